@@ -0,0 +1,283 @@
+//! Aggregate the values of a series over time windows instead of writing every sample
+
+use std::collections::BTreeMap;
+use std::num::NonZeroU64;
+
+use serde::Deserialize;
+
+use crate::point::{FieldValue, Point, seconds_to_nanos};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Function {
+    Mean,
+    Min,
+    Max,
+    Last,
+    Count,
+}
+
+impl Function {
+    const fn suffix(self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Last => "last",
+            Self::Count => "count",
+        }
+    }
+}
+
+/// Length of the windows and the fields written per window
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Aggregate {
+    /// Windows are aligned to multiples of their length since UNIX epoch
+    pub window_seconds: NonZeroU64,
+
+    /// Each function writes a field like `power_mean`
+    #[serde(default = "default_functions")]
+    pub functions: Vec<Function>,
+}
+
+fn default_functions() -> Vec<Function> {
+    vec![
+        Function::Mean,
+        Function::Min,
+        Function::Max,
+        Function::Last,
+        Function::Count,
+    ]
+}
+
+/// Measurement and tags
+type Series = (String, Vec<(String, String)>);
+
+#[derive(Debug)]
+struct Window {
+    /// Topic of the last message to find the rule again when the window is written
+    topic: String,
+    end: u128,
+    functions: Vec<Function>,
+    fields: Vec<(String, Stats)>,
+}
+
+#[derive(Debug)]
+struct Stats {
+    count: i64,
+    last: FieldValue,
+    /// `None` when any value is not a number
+    numeric: Option<Numeric>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Numeric {
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Stats {
+    fn new(value: FieldValue) -> Self {
+        let numeric = value.as_f64().map(|value| Numeric {
+            sum: value,
+            min: value,
+            max: value,
+        });
+        Self {
+            count: 1,
+            last: value,
+            numeric,
+        }
+    }
+
+    fn add(&mut self, value: FieldValue) {
+        self.count = self.count.saturating_add(1);
+        self.numeric = self
+            .numeric
+            .zip(value.as_f64())
+            .map(|(numeric, value)| Numeric {
+                sum: numeric.sum + value,
+                min: numeric.min.min(value),
+                max: numeric.max.max(value),
+            });
+        self.last = value;
+    }
+
+    fn result(&self, function: Function) -> Option<FieldValue> {
+        match function {
+            #[expect(clippy::cast_precision_loss)]
+            Function::Mean => self
+                .numeric
+                .map(|numeric| FieldValue::Float(numeric.sum / self.count as f64)),
+            Function::Min => self.numeric.map(|numeric| FieldValue::Float(numeric.min)),
+            Function::Max => self.numeric.map(|numeric| FieldValue::Float(numeric.max)),
+            Function::Last => Some(self.last.clone()),
+            Function::Count => Some(FieldValue::Integer(self.count)),
+        }
+    }
+}
+
+/// Open windows by their start and series
+#[derive(Debug, Default)]
+pub struct Windows {
+    windows: BTreeMap<(u128, Series), Window>,
+    /// Earliest end of the open windows to skip flushes which have nothing to do
+    next_end: Option<u128>,
+}
+
+impl Windows {
+    pub fn add(&mut self, aggregate: &Aggregate, topic: &str, points: Vec<Point>) {
+        let length = seconds_to_nanos(aggregate.window_seconds.get());
+        for point in points {
+            let start = point.timestamp - point.timestamp % length;
+            let window = self
+                .windows
+                .entry((start, (point.measurement, point.tags)))
+                .or_insert_with(|| Window {
+                    topic: topic.to_owned(),
+                    end: start + length,
+                    functions: aggregate.functions.clone(),
+                    fields: Vec::new(),
+                });
+            topic.clone_into(&mut window.topic);
+            for (key, value) in point.fields {
+                match window
+                    .fields
+                    .iter_mut()
+                    .find(|(existing, _)| *existing == key)
+                {
+                    Some((_, stats)) => stats.add(value),
+                    None => window.fields.push((key, Stats::new(value))),
+                }
+            }
+            self.next_end = Some(
+                self.next_end
+                    .map_or(window.end, |next| next.min(window.end)),
+            );
+        }
+    }
+
+    /// Points of the windows which ended until now with their topic, timestamped with the start of their window
+    pub fn flush(&mut self, now: u128) -> Vec<(String, Point)> {
+        if self.next_end.is_none_or(|next| next > now) {
+            return Vec::new();
+        }
+        let mut points = Vec::new();
+        for ((start, (measurement, tags)), window) in
+            self.windows.extract_if(.., |_, window| window.end <= now)
+        {
+            let mut point = Point::new(measurement, start);
+            point.tags = tags;
+            for (key, stats) in window.fields {
+                for function in &window.functions {
+                    if let Some(value) = stats.result(*function) {
+                        point
+                            .fields
+                            .push((format!("{key}_{}", function.suffix()), value));
+                    }
+                }
+            }
+            if !point.fields.is_empty() {
+                points.push((window.topic, point));
+            }
+        }
+        self.next_end = self.windows.values().map(|window| window.end).min();
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{SECOND, point};
+
+    fn aggregate(functions: &str) -> Aggregate {
+        toml::from_str(&format!("window_seconds = 10\n{functions}")).unwrap()
+    }
+
+    fn render(points: Vec<(String, Point)>) -> Vec<String> {
+        let points = points
+            .into_iter()
+            .map(|(_, point)| point)
+            .collect::<Vec<_>>();
+        crate::testing::render(&points)
+    }
+
+    #[test]
+    fn aggregate_works() {
+        let mut windows = Windows::default();
+        let aggregate = aggregate("");
+        windows.add(
+            &aggregate,
+            "meter",
+            [(11, 2.0), (12, 4.0), (19, 3.0), (21, 5.0)]
+                .map(|(seconds, value)| point(seconds, "power", value))
+                .to_vec(),
+        );
+        assert_eq!(windows.flush(19 * SECOND), Vec::new());
+        assert_eq!(
+            render(windows.flush(20 * SECOND)),
+            [
+                "measurement,topic=meter power_mean=3,power_min=2,power_max=4,power_last=3,power_count=3i 10000000000"
+            ]
+        );
+        assert_eq!(
+            render(windows.flush(30 * SECOND)),
+            [
+                "measurement,topic=meter power_mean=5,power_min=5,power_max=5,power_last=5,power_count=1i 20000000000"
+            ]
+        );
+        assert_eq!(windows.flush(u128::MAX), Vec::new());
+    }
+
+    #[test]
+    fn selected_functions() {
+        let mut windows = Windows::default();
+        let aggregate = aggregate(r#"functions = ["max", "count"]"#);
+        windows.add(
+            &aggregate,
+            "meter",
+            vec![point(1, "power", 1.0), point(2, "power", 2.0)],
+        );
+        assert_eq!(
+            render(windows.flush(u128::MAX)),
+            ["measurement,topic=meter power_max=2,power_count=2i 0"]
+        );
+    }
+
+    #[test]
+    fn non_numeric_values_only_have_last_and_count() {
+        let mut windows = Windows::default();
+        let aggregate = aggregate("");
+        windows.add(
+            &aggregate,
+            "meter",
+            vec![
+                point(1, "power", FieldValue::Integer(1)),
+                point(2, "power", FieldValue::String("on".to_owned())),
+            ],
+        );
+        assert_eq!(
+            render(windows.flush(u128::MAX)),
+            [r#"measurement,topic=meter power_last="on",power_count=2i 0"#]
+        );
+    }
+
+    #[test]
+    fn series_are_independent() {
+        let mut windows = Windows::default();
+        let aggregate = aggregate(r#"functions = ["last"]"#);
+        let mut other = point(1, "power", 2.0);
+        other.tags[0].1 = "other".to_owned();
+        windows.add(&aggregate, "meter", vec![point(1, "power", 1.0), other]);
+        assert_eq!(
+            render(windows.flush(u128::MAX)),
+            [
+                "measurement,topic=meter power_last=1 0",
+                "measurement,topic=other power_last=2 0",
+            ]
+        );
+    }
+}
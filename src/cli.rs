@@ -1,14 +1,52 @@
-use clap::{ArgGroup, Parser, ValueHint};
+use clap::{ArgGroup, Parser, Subcommand, ValueHint};
+
+/// Where the points are written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Influxdb,
+    Stdout,
+    File(std::path::PathBuf),
+}
+
+impl std::str::FromStr for Output {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "influxdb" => Ok(Self::Influxdb),
+            "stdout" => Ok(Self::Stdout),
+            _ => input
+                .strip_prefix("file:")
+                .filter(|path| !path.is_empty())
+                .map(|path| Self::File(path.into()))
+                .ok_or_else(|| "possible values: influxdb, stdout, file:PATH".to_owned()),
+        }
+    }
+}
 
 #[expect(clippy::doc_markdown)]
 #[derive(Debug, Parser)]
-#[command(about, version)]
+#[command(about, version, subcommand_precedence_over_arg = true)]
 #[command(group(
     ArgGroup::new("influxtarget")
         .args(&["influx_org", "influx_database", "victoria_metrics"])
-        .required(true)
 ))]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Where to write the points to.
+    ///
+    /// `influxdb` sends them to the database.
+    /// `stdout` or `file:PATH` write the line protocol instead of sending it which is helpful to test topics without touching the database.
+    #[arg(
+        long, env,
+        value_hint = ValueHint::Other,
+        value_name = "OUTPUT",
+        default_value = "influxdb",
+    )]
+    pub output: Output,
+
     /// HTTP address of InfluxDB
     #[arg(
         long, env,
@@ -123,7 +161,82 @@ pub struct Cli {
     )]
     pub mqtt_topics: Vec<String>,
 
-    /// Show more details
+    /// TOML file with rules on how to convert payloads per topic.
+    ///
+    /// Each `[[rule]]` has a `topic` filter and the first matching rule is used.
+    /// `keys.include` and `keys.exclude` select payload keys like `update.*` and `keys.rename` renames them like `{ temperature_c = "temperature" }`.
+    #[arg(
+        long, env,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        help_heading = "Filter",
+    )]
+    pub config: Option<std::path::PathBuf>,
+
+    /// Only convert messages with topics matching one of these regular expressions.
+    ///
+    /// Applied after the MQTT subscription so the topics can be subscribed broadly.
+    #[arg(
+        long, env,
+        value_hint = ValueHint::Other,
+        value_name = "REGEX",
+        help_heading = "Filter",
+    )]
+    pub include_topic_regex: Vec<regex::Regex>,
+
+    /// Do not convert messages with topics matching one of these MQTT topic filters.
+    ///
+    /// Supports the MQTT wildcards `+` and `#` like `+/+/set` or `zigbee2mqtt/bridge/#`.
+    #[arg(
+        long, env,
+        value_hint = ValueHint::Other,
+        value_name = "TOPIC",
+        help_heading = "Filter",
+    )]
+    pub exclude_topic: Vec<String>,
+
+    /// Do not convert messages with topics matching one of these regular expressions
+    #[arg(
+        long, env,
+        value_hint = ValueHint::Other,
+        value_name = "REGEX",
+        help_heading = "Filter",
+    )]
+    pub exclude_topic_regex: Vec<regex::Regex>,
+
+    /// Append every received MQTT message to this file.
+    ///
+    /// Includes the raw payload, retained and duplicate messages.
+    /// The file can be used with the `replay` subcommand.
+    #[arg(
+        long, env,
+        value_hint = ValueHint::FilePath,
+        value_name = "PATH",
+        help_heading = "Record",
+    )]
+    pub record: Option<std::path::PathBuf>,
+
+    /// Rotate the record file when it grows beyond this size
+    #[arg(
+        long, env,
+        value_hint = ValueHint::Other,
+        value_name = "BYTES",
+        help_heading = "Record",
+        default_value = "104857600",
+    )]
+    pub record_max_bytes: u64,
+
+    /// Amount of rotated record files to keep
+    #[arg(
+        long, env,
+        value_hint = ValueHint::Other,
+        value_name = "INT",
+        help_heading = "Record",
+        default_value = "3",
+    )]
+    pub record_keep: usize,
+
+    /// Show more details like the subscribed topic filter each MQTT message matched
     #[arg(short, long)]
     pub verbose: bool,
 
@@ -146,6 +259,22 @@ pub struct Cli {
     pub buffer_seconds: f32,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Replay recorded MQTT messages from a file instead of connecting to the MQTT broker.
+    ///
+    /// Each line is either JSON like `{"timestamp": 1337, "topic": "foo/bar", "payload": "NDI="}` with the timestamp in nanoseconds since UNIX epoch and a base64 encoded payload as `--record` creates,
+    /// or a `mosquitto_sub -v` line (`topic payload`) optionally prefixed by the timestamp `mosquitto_sub -F '%U %t %p'` creates.
+    /// Lines without a timestamp use the time of the replay.
+    ///
+    /// Only messages matching the given MQTT topics are replayed.
+    Replay {
+        /// Recording to replay
+        #[arg(value_hint = ValueHint::FilePath)]
+        file: std::path::PathBuf,
+    },
+}
+
 #[test]
 fn verify() {
     use clap::CommandFactory as _;
@@ -0,0 +1,182 @@
+//! Only write values which changed since the last written value of the same series
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::point::{FieldValue, Point, SeriesKey, seconds_to_nanos};
+
+/// Thresholds of the change needed to write a value
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deadband {
+    /// Minimal absolute change like `0.5`
+    #[serde(default)]
+    pub absolute: f64,
+
+    /// Minimal change relative to the last written value like `0.01` for 1 %
+    #[serde(default)]
+    pub relative: f64,
+
+    /// Write the value anyway when the last written value of the series is older so gaps are distinguishable from unchanged values
+    pub heartbeat_seconds: Option<u64>,
+}
+
+impl Deadband {
+    /// Numbers of any kind are compared with the thresholds, other values only by equality
+    fn is_changed(&self, last: &FieldValue, value: &FieldValue) -> bool {
+        match (last.as_f64(), value.as_f64()) {
+            (Some(last), Some(value)) => {
+                let band = self.absolute.max(self.relative * last.abs());
+                (value - last).abs() > band
+            }
+            _ => last != value,
+        }
+    }
+}
+
+/// Last written value and its timestamp per series
+#[derive(Debug, Default)]
+pub struct LastWritten(HashMap<SeriesKey, (FieldValue, u128)>);
+
+impl LastWritten {
+    /// Remove the fields which did not change enough. Points without fields left are removed.
+    pub fn filter(&mut self, deadband: &Deadband, points: Vec<Point>) -> Vec<Point> {
+        let heartbeat = deadband.heartbeat_seconds.map(seconds_to_nanos);
+        points
+            .into_iter()
+            .filter_map(|mut point| {
+                let mut fields = std::mem::take(&mut point.fields);
+                fields.retain(|(key, value)| {
+                    let series = point.series_key(key);
+                    if let Some((last, timestamp)) = self.0.get(&series) {
+                        let is_heartbeat = heartbeat.is_some_and(|heartbeat| {
+                            point.timestamp.saturating_sub(*timestamp) >= heartbeat
+                        });
+                        if !is_heartbeat && !deadband.is_changed(last, value) {
+                            return false;
+                        }
+                    }
+                    self.0.insert(series, (value.clone(), point.timestamp));
+                    true
+                });
+                point.fields = fields;
+                (!point.fields.is_empty()).then_some(point)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::point;
+
+    /// Values of the written points
+    fn written(deadband: &Deadband, values: &[(u128, f64)]) -> Vec<f64> {
+        let mut last = LastWritten::default();
+        values
+            .iter()
+            .flat_map(|(seconds, value)| {
+                last.filter(deadband, vec![point(*seconds, "value", *value)])
+            })
+            .map(|point| match point.fields[0].1 {
+                FieldValue::Float(value) => value,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn change_only() {
+        let deadband = Deadband::default();
+        let values = [(0, 1.0), (1, 1.0), (2, 2.0), (3, 2.0), (4, 1.0)];
+        assert_eq!(written(&deadband, &values), [1.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn absolute() {
+        let deadband = Deadband {
+            absolute: 0.5,
+            ..Deadband::default()
+        };
+        let values = [(0, 20.0), (1, 20.3), (2, 20.5), (3, 20.6), (4, 19.9)];
+        assert_eq!(written(&deadband, &values), [20.0, 20.6, 19.9]);
+    }
+
+    #[test]
+    fn relative() {
+        let deadband = Deadband {
+            relative: 0.1,
+            ..Deadband::default()
+        };
+        let values = [(0, 1000.0), (1, 1090.0), (2, 1101.0), (3, 1150.0)];
+        assert_eq!(written(&deadband, &values), [1000.0, 1101.0]);
+    }
+
+    #[test]
+    fn integers() {
+        let mut last = LastWritten::default();
+        let deadband = Deadband {
+            absolute: 5.0,
+            ..Deadband::default()
+        };
+        let written = [(0, 100), (1, 103), (2, 106), (3, 102)]
+            .into_iter()
+            .flat_map(|(seconds, value)| {
+                last.filter(
+                    &deadband,
+                    vec![point(seconds, "value", FieldValue::Integer(value))],
+                )
+            })
+            .map(|point| point.fields[0].1.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            written,
+            [FieldValue::Integer(100), FieldValue::Integer(106)]
+        );
+    }
+
+    #[test]
+    fn heartbeat() {
+        let deadband = Deadband {
+            heartbeat_seconds: Some(60),
+            ..Deadband::default()
+        };
+        let values = [(0, 1.0), (30, 1.0), (60, 1.0), (90, 1.0), (120, 1.0)];
+        assert_eq!(written(&deadband, &values), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn series_are_independent() {
+        let mut last = LastWritten::default();
+        let deadband = Deadband::default();
+        let mut other = point(1, "value", 1.0);
+        other.tags[0].1 = "other".to_owned();
+        assert_eq!(
+            last.filter(&deadband, vec![point(0, "value", 1.0)]).len(),
+            1
+        );
+        assert_eq!(last.filter(&deadband, vec![other]).len(), 1);
+        assert_eq!(
+            last.filter(&deadband, vec![point(2, "value", 1.0)]).len(),
+            0
+        );
+    }
+
+    #[test]
+    fn unchanged_fields_are_removed() {
+        let mut last = LastWritten::default();
+        let deadband = Deadband::default();
+        let mut first = point(0, "a", 1.0);
+        first.add_field("b", 1.0);
+        let mut second = point(1, "a", 1.0);
+        second.add_field("b", 2.0);
+        assert_eq!(last.filter(&deadband, vec![first]).len(), 1);
+        let written = last.filter(&deadband, vec![second]);
+        assert_eq!(
+            written[0].fields,
+            [("b".to_owned(), FieldValue::Float(2.0))]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Drop messages which were already received shortly before, like repeated publishes of bridged brokers
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash as _, Hasher as _};
+use std::num::NonZeroU64;
+
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::floatify::Numbers;
+use crate::message::Message;
+use crate::payload::{Format, Payload};
+use crate::point::seconds_to_nanos;
+
+/// Time window in which repeated messages are dropped
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dedup {
+    pub window_seconds: NonZeroU64,
+
+    /// Identify messages by the topic and this payload value like `$.message_id` instead of the whole payload
+    pub id: Option<JsonPath>,
+}
+
+impl Dedup {
+    fn key(&self, message: &Message, format: &Format, numbers: Numbers) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        message.topic().hash(&mut hasher);
+        match self.id(message, format, numbers) {
+            Some(id) => id.hash(&mut hasher),
+            None => message.payload().hash(&mut hasher),
+        }
+        hasher.finish()
+    }
+
+    fn id(&self, message: &Message, format: &Format, numbers: Numbers) -> Option<String> {
+        let path = self.id.as_ref()?;
+        let payload = Payload::new(message.payload().to_vec(), format, numbers)?;
+        let json = payload.as_json()?;
+        match path.query(&json).first()? {
+            Value::String(string) => Some(string.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            Value::Null | Value::Bool(_) | Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+}
+
+/// Keys of the received messages until their window expires
+#[derive(Debug, Default)]
+pub struct Seen {
+    expires: HashMap<u64, u128>,
+    /// Keys in the order they were received to remove expired ones
+    order: VecDeque<(u128, u64)>,
+}
+
+impl Seen {
+    /// Remember the message and check if it was already received within the window
+    pub fn is_duplicate(
+        &mut self,
+        dedup: &Dedup,
+        message: &Message,
+        format: &Format,
+        numbers: Numbers,
+    ) -> bool {
+        let now = message.nanos();
+        while let Some((expires, key)) = self.order.front().copied()
+            && expires <= now
+        {
+            self.order.pop_front();
+            if self.expires.get(&key) == Some(&expires) {
+                self.expires.remove(&key);
+            }
+        }
+
+        let key = dedup.key(message, format, numbers);
+        if self.expires.get(&key).is_some_and(|expires| *expires > now) {
+            return true;
+        }
+        let expires = now + seconds_to_nanos(dedup.window_seconds.get());
+        self.expires.insert(key, expires);
+        self.order.push_back((expires, key));
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SECOND;
+
+    fn duplicates(dedup: &str, messages: &[(u128, &str, &str)]) -> Vec<bool> {
+        let dedup = toml::from_str::<Dedup>(dedup).unwrap();
+        let mut seen = Seen::default();
+        messages
+            .iter()
+            .map(|(seconds, topic, payload)| {
+                let message =
+                    Message::new(seconds * SECOND, (*topic).to_owned(), (*payload).into());
+                seen.is_duplicate(&dedup, &message, &Format::Auto, Numbers::default())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn payload_within_window() {
+        assert_eq!(
+            duplicates(
+                "window_seconds = 10",
+                &[
+                    (0, "foo", "42"),
+                    (1, "foo", "42"),
+                    (2, "bar", "42"),
+                    (3, "foo", "43"),
+                    (9, "foo", "42"),
+                    (10, "foo", "42"),
+                    (11, "foo", "42"),
+                ]
+            ),
+            [false, true, false, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn message_id() {
+        assert_eq!(
+            duplicates(
+                r#"
+                window_seconds = 10
+                id = "$.id"
+                "#,
+                &[
+                    (0, "foo", r#"{"id": 1, "value": 42}"#),
+                    (1, "foo", r#"{"id": 1, "value": 43}"#),
+                    (2, "foo", r#"{"id": 2, "value": 42}"#),
+                    (3, "foo", "42"),
+                    (4, "foo", "42"),
+                ]
+            ),
+            [false, true, false, false, true]
+        );
+    }
+}
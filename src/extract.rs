@@ -0,0 +1,100 @@
+//! Select values from a payload with [JSONPath](https://www.rfc-editor.org/rfc/rfc9535) expressions
+//! instead of flattening the whole payload.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use crate::floatify::{self, floatify};
+
+/// Named fields and tags selected by JSONPath expressions like `$.data.meter.power`.
+///
+/// When an expression selects multiple values the first one is used.
+#[expect(clippy::doc_markdown)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Extract {
+    #[serde(default)]
+    pub fields: BTreeMap<String, JsonPath>,
+
+    #[serde(default)]
+    pub tags: BTreeMap<String, JsonPath>,
+}
+
+impl Extract {
+    pub fn fields(&self, value: &Value, options: floatify::Options<'_>) -> Vec<(String, f64)> {
+        self.fields
+            .iter()
+            .filter_map(|(name, path)| {
+                let value = match path.query(value).first()? {
+                    Value::Bool(true) => Some(1.0),
+                    Value::Bool(false) => Some(0.0),
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(string) => floatify(string, options),
+                    Value::Null | Value::Array(_) | Value::Object(_) => None,
+                }?;
+                Some((name.clone(), value))
+            })
+            .collect()
+    }
+
+    pub fn tags(&self, value: &Value) -> Vec<(String, String)> {
+        self.tags
+            .iter()
+            .filter_map(|(name, path)| {
+                let value = match path.query(value).first()? {
+                    Value::Bool(bool) => bool.to_string(),
+                    Value::Number(number) => number.to_string(),
+                    Value::String(string) => string.clone(),
+                    Value::Null | Value::Array(_) | Value::Object(_) => return None,
+                };
+                Some((name.clone(), value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn example() -> Extract {
+        toml::from_str(
+            r#"
+            fields = { power = "$.data.meter.power", energy = "$.data.meter.total", missing = "$.nope" }
+            tags = { meter = "$.data.meter.id", phase = "$.data.phases[0]" }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fields_works() {
+        let payload = json!({"data": {"meter": {"id": "abc", "power": 42, "total": "13.37 kWh"}}});
+        assert_eq!(
+            example().fields(&payload, floatify::Options::default()),
+            [("energy".to_owned(), 13.37), ("power".to_owned(), 42.0)]
+        );
+    }
+
+    #[test]
+    fn tags_works() {
+        let payload = json!({"data": {"meter": {"id": "abc"}, "phases": [1, 2, 3]}});
+        assert_eq!(
+            example().tags(&payload),
+            [
+                ("meter".to_owned(), "abc".to_owned()),
+                ("phase".to_owned(), "1".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_path_fails() {
+        assert!(toml::from_str::<Extract>(r#"fields = { power = "data.power" }"#).is_err());
+    }
+}
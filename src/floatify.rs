@@ -1,19 +1,140 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// User defined mapping of states like `open` or `heating` to numbers. Case insensitive.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(from = "HashMap<String, f64>")]
+pub struct States(HashMap<String, f64>);
+
+impl From<HashMap<String, f64>> for States {
+    fn from(states: HashMap<String, f64>) -> Self {
+        Self(
+            states
+                .into_iter()
+                .map(|(state, value)| (state.to_lowercase(), value))
+                .collect(),
+        )
+    }
+}
+
+impl States {
+    pub fn get(&self, input: &str) -> Option<f64> {
+        if self.0.is_empty() {
+            return None;
+        }
+        self.0.get(&input.trim().to_lowercase()).copied()
+    }
+
+    /// Add the states of the other which are not already defined
+    pub fn extend_missing(&mut self, other: &Self) {
+        for (state, value) in &other.0 {
+            self.0.entry(state.clone()).or_insert(*value);
+        }
+    }
+}
+
+/// Number formats besides `1234.5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Numbers {
+    /// Decimal separator like `,` for `21,5`
+    #[serde(default = "default_decimal")]
+    pub decimal: char,
+
+    /// Thousands separator which is ignored like `.` for `1.234,5`
+    pub thousands: Option<char>,
+
+    /// Parse hexadecimal `0x1F` and binary `0b101` integers
+    #[serde(default)]
+    pub radix_prefixes: bool,
+}
+
+const fn default_decimal() -> char {
+    '.'
+}
+
+impl Default for Numbers {
+    fn default() -> Self {
+        Self {
+            decimal: default_decimal(),
+            thousands: None,
+            radix_prefixes: false,
+        }
+    }
+}
+
+/// How strings are converted into floats
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Options<'o> {
+    /// Checked before the built-in states like `on` and `off`
+    pub states: Option<&'o States>,
+
+    pub numbers: Numbers,
+}
+
 /// Assume floats of the input string, otherwise return None
-pub fn floatify(input: &str) -> Option<f64> {
+pub fn floatify(input: &str, options: Options<'_>) -> Option<f64> {
+    if let Some(value) = options.states.and_then(|states| states.get(input)) {
+        return Some(value);
+    }
     match input {
         "true" | "True" | "TRUE" | "on" | "On" | "ON" | "online" | "Online" | "ONLINE" => Some(1.0),
         "false" | "False" | "FALSE" | "off" | "Off" | "OFF" | "offline" | "Offline" | "OFFLINE" => {
             Some(0.0)
         }
-        input => input
-            .split(char::is_whitespace)
-            .find(|part| !part.is_empty())? // lazy trim
-            .parse::<f64>()
-            .ok()
-            .filter(|float| float.is_finite()),
+        input => parse_number(
+            input
+                .split(char::is_whitespace)
+                .find(|part| !part.is_empty())?, // lazy trim
+            options.numbers,
+        ),
     }
 }
 
+/// Parse a single number without surrounding whitespace
+pub fn parse_number(input: &str, numbers: Numbers) -> Option<f64> {
+    if numbers.radix_prefixes
+        && let Some(int) = parse_radix_prefixed(input)
+    {
+        return Some(int);
+    }
+    let float = if numbers == Numbers::default() {
+        input.parse::<f64>().ok()?
+    } else {
+        input
+            .chars()
+            .filter(|char| Some(*char) != numbers.thousands)
+            .map(|char| if char == numbers.decimal { '.' } else { char })
+            .collect::<String>()
+            .parse::<f64>()
+            .ok()?
+    };
+    float.is_finite().then_some(float)
+}
+
+#[expect(clippy::cast_precision_loss)]
+fn parse_radix_prefixed(input: &str) -> Option<f64> {
+    let (negative, input) = input
+        .strip_prefix('-')
+        .map_or((false, input), |input| (true, input));
+    let (radix, digits) = if let Some(digits) = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        (16, digits)
+    } else if let Some(digits) = input
+        .strip_prefix("0b")
+        .or_else(|| input.strip_prefix("0B"))
+    {
+        (2, digits)
+    } else {
+        return None;
+    };
+    let int = u64::from_str_radix(digits, radix).ok()? as f64;
+    Some(if negative { -int } else { int })
+}
+
 #[cfg(test)]
 #[rstest::rstest]
 #[case::int("42", 42.0)]
@@ -25,7 +146,11 @@ pub fn floatify(input: &str) -> Option<f64> {
 #[case::units("12.3 °C", 12.3)]
 #[case::units(" 12.3 °C", 12.3)]
 fn test_some(#[case] input: &str, #[case] expected: f64) {
-    float_eq::assert_float_eq!(floatify(dbg!(input)).unwrap(), expected, abs <= 0.001);
+    float_eq::assert_float_eq!(
+        floatify(dbg!(input), Options::default()).unwrap(),
+        expected,
+        abs <= 0.001
+    );
 }
 
 #[cfg(test)]
@@ -38,5 +163,56 @@ fn test_some(#[case] input: &str, #[case] expected: f64) {
 #[case::non_finite("inf")]
 #[case::non_finite("infinity")]
 fn test_none(#[case] input: &str) {
-    assert_eq!(floatify(dbg!(input)), None);
+    assert_eq!(floatify(dbg!(input), Options::default()), None);
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::custom("open", Some(1.0))]
+#[case::case_insensitive("CLOSED", Some(0.0))]
+#[case::enum_state("Heating", Some(2.0))]
+#[case::overrides_builtin("on", Some(5.0))]
+#[case::builtin("off", Some(0.0))]
+#[case::unknown("cooling", None)]
+fn test_states(#[case] input: &str, #[case] expected: Option<f64>) {
+    let states = toml::from_str::<States>("open = 1\nClosed = 0\nheating = 2\nON = 5").unwrap();
+    let options = Options {
+        states: Some(&states),
+        ..Options::default()
+    };
+    assert_eq!(floatify(input, options), expected);
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::decimal_comma("21,5", ',', None, Some(21.5))]
+#[case::thousands("1.234,5", ',', Some('.'), Some(1234.5))]
+#[case::thousands_comma("1,234.5", '.', Some(','), Some(1234.5))]
+#[case::thousands_with_unit("1.234,5 kWh", ',', Some('.'), Some(1234.5))]
+#[case::hex("0x1F", '.', None, Some(31.0))]
+#[case::hex_upper("0X1f", '.', None, Some(31.0))]
+#[case::negative_hex("-0x10", '.', None, Some(-16.0))]
+#[case::binary("0b101", '.', None, Some(5.0))]
+#[case::invalid_hex("0xZZ", '.', None, None)]
+fn test_numbers(
+    #[case] input: &str,
+    #[case] decimal: char,
+    #[case] thousands: Option<char>,
+    #[case] expected: Option<f64>,
+) {
+    let options = Options {
+        numbers: Numbers {
+            decimal,
+            thousands,
+            radix_prefixes: true,
+        },
+        ..Options::default()
+    };
+    assert_eq!(floatify(input, options), expected);
+}
+
+#[test]
+fn radix_prefixes_are_opt_in() {
+    assert_eq!(floatify("0x1F", Options::default()), None);
+    assert_eq!(floatify("21,5", Options::default()), None);
 }
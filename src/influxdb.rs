@@ -5,6 +5,9 @@ use reqwest::header;
 use tokio::time::sleep;
 use url::Url;
 
+use crate::line_protocol;
+use crate::point::Point;
+
 const USER_AGENT: &str = concat!(
     env!("CARGO_PKG_NAME"),
     "/",
@@ -95,14 +98,15 @@ impl Influxdb {
         &self.write_url
     }
 
-    /// Append to the lines that will be written
-    pub fn append(&mut self, mut lines: Vec<String>) {
-        if self.verbose {
-            for line in &lines {
+    /// Append to the points that will be written
+    pub fn append(&mut self, points: Vec<Point>) {
+        for point in points {
+            let line = line_protocol::render(&point);
+            if self.verbose {
                 println!("InfluxDB Line: {line}");
             }
+            self.linebuffer.push(line);
         }
-        self.linebuffer.append(&mut lines);
     }
 
     async fn write(&mut self) -> anyhow::Result<()> {
@@ -0,0 +1,324 @@
+//! Render and parse [`Point`]s as [Line Protocol](https://docs.influxdata.com/influxdb/v2.1/reference/syntax/line-protocol/)
+
+use std::fmt::Write as _;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use anyhow::Context as _;
+
+use crate::point::{FieldValue, Point};
+
+/// Escape rules differ between the element of a line.
+///
+/// See <https://docs.influxdata.com/influxdb/v2.1/reference/syntax/line-protocol/#special-characters>
+pub mod escape {
+    fn escape(input: &str, special: &[char]) -> String {
+        let mut result = String::with_capacity(input.len());
+        for char in input.chars() {
+            match char {
+                // Line protocol is line based, there is no way to escape a newline
+                '\n' | '\r' => {}
+                char if char == '\\' || special.contains(&char) => {
+                    result.push('\\');
+                    result.push(char);
+                }
+                char => result.push(char),
+            }
+        }
+        result
+    }
+
+    pub fn measurement(input: &str) -> String {
+        escape(input, &[',', ' '])
+    }
+
+    pub fn tag_key(input: &str) -> String {
+        escape(input, &[',', '=', ' '])
+    }
+
+    pub fn tag_value(input: &str) -> String {
+        escape(input, &[',', '=', ' '])
+    }
+
+    pub fn field_key(input: &str) -> String {
+        escape(input, &[',', '=', ' '])
+    }
+
+    pub fn field_string(input: &str) -> String {
+        escape(input, &['"'])
+    }
+}
+
+pub fn render(point: &Point) -> String {
+    let mut line = escape::measurement(&point.measurement);
+    for (key, value) in &point.tags {
+        let key = escape::tag_key(key);
+        let value = escape::tag_value(value);
+        // Tags without key or value are not valid in line protocol
+        if key.is_empty() || value.is_empty() {
+            continue;
+        }
+        _ = write!(line, ",{key}={value}");
+    }
+    for (index, (key, value)) in point.fields.iter().enumerate() {
+        let separator = if index == 0 { ' ' } else { ',' };
+        _ = write!(line, "{separator}{}=", escape::field_key(key));
+        _ = match value {
+            FieldValue::Float(float) => write!(line, "{float}"),
+            FieldValue::Integer(int) => write!(line, "{int}i"),
+            FieldValue::UInteger(int) => write!(line, "{int}u"),
+            FieldValue::String(string) => write!(line, "\"{}\"", escape::field_string(string)),
+            FieldValue::Boolean(bool) => write!(line, "{bool}"),
+        };
+    }
+    _ = write!(line, " {}", point.timestamp);
+    line
+}
+
+/// Parse a single line of line protocol.
+///
+/// Lines without a timestamp get the given timestamp.
+pub fn parse(line: &str, timestamp: u128) -> anyhow::Result<Point> {
+    /// Read until one of the unescaped terminators and return the unescaped content
+    fn read_until(chars: &mut Peekable<Chars<'_>>, terminators: &[char]) -> String {
+        let mut result = String::new();
+        while let Some(&char) = chars.peek() {
+            if terminators.contains(&char) {
+                break;
+            }
+            chars.next();
+            match chars.peek() {
+                Some(&next) if char == '\\' && ESCAPABLE.contains(&next) => {
+                    result.push(next);
+                    chars.next();
+                }
+                _ => result.push(char),
+            }
+        }
+        result
+    }
+    const ESCAPABLE: [char; 5] = [',', '=', ' ', '"', '\\'];
+
+    fn read_string(chars: &mut Peekable<Chars<'_>>) -> anyhow::Result<String> {
+        let mut result = String::new();
+        loop {
+            match chars.next().context("unterminated string")? {
+                '"' => return Ok(result),
+                '\\' if chars.peek().is_some_and(|next| ['"', '\\'].contains(next)) => {
+                    result.extend(chars.next());
+                }
+                char => result.push(char),
+            }
+        }
+    }
+
+    fn field_value(chars: &mut Peekable<Chars<'_>>) -> anyhow::Result<FieldValue> {
+        if chars.next_if_eq(&'"').is_some() {
+            return read_string(chars).map(FieldValue::String);
+        }
+        let value = read_until(chars, &[',', ' ']);
+        let parsed = match value.as_str() {
+            "t" | "T" | "true" | "True" | "TRUE" => FieldValue::Boolean(true),
+            "f" | "F" | "false" | "False" | "FALSE" => FieldValue::Boolean(false),
+            _ => {
+                if let Some(int) = value.strip_suffix('i') {
+                    FieldValue::Integer(int.parse()?)
+                } else if let Some(int) = value.strip_suffix('u') {
+                    FieldValue::UInteger(int.parse()?)
+                } else {
+                    let float = value.parse::<f64>()?;
+                    anyhow::ensure!(float.is_finite(), "field value is not finite");
+                    FieldValue::Float(float)
+                }
+            }
+        };
+        Ok(parsed)
+    }
+
+    let mut chars = line.trim_end().chars().peekable();
+    let measurement = read_until(&mut chars, &[',', ' ']);
+    anyhow::ensure!(!measurement.is_empty(), "measurement is empty");
+    let mut point = Point::new(measurement, timestamp);
+    while chars.next_if_eq(&',').is_some() {
+        let key = read_until(&mut chars, &['=', ',', ' ']);
+        anyhow::ensure!(chars.next() == Some('='), "tag {key:?} has no value");
+        let value = read_until(&mut chars, &[',', ' ']);
+        anyhow::ensure!(
+            !key.is_empty() && !value.is_empty(),
+            "tag key and value must not be empty"
+        );
+        point.tags.push((key, value));
+    }
+    anyhow::ensure!(chars.next() == Some(' '), "fields are missing");
+    loop {
+        let key = read_until(&mut chars, &['=', ',', ' ']);
+        anyhow::ensure!(chars.next() == Some('='), "field {key:?} has no value");
+        anyhow::ensure!(!key.is_empty(), "field key must not be empty");
+        let value = field_value(&mut chars).with_context(|| format!("field {key:?}"))?;
+        point.fields.push((key, value));
+        match chars.next() {
+            Some(',') => {}
+            Some(' ') => {
+                let timestamp = chars.collect::<String>();
+                point.timestamp = timestamp
+                    .parse()
+                    .with_context(|| format!("invalid timestamp {timestamp:?}"))?;
+                break;
+            }
+            None => break,
+            Some(char) => anyhow::bail!("unexpected {char:?} after a field value"),
+        }
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn render_works() {
+        let mut point = Point::new("measurement", 1337);
+        point.tags.push(("topic".to_owned(), "foo bar".to_owned()));
+        point.tags.push(("key1".to_owned(), "a,b".to_owned()));
+        point.add_field("value", 42.0);
+        point.add_field("other", 13.37);
+        assert_eq!(
+            render(&point),
+            r"measurement,topic=foo\ bar,key1=a\,b value=42,other=13.37 1337"
+        );
+    }
+
+    #[test]
+    fn render_escapes_all_special_characters() {
+        let mut point = Point::new("m e,a=s", 1337);
+        point
+            .tags
+            .push(("k=e y".to_owned(), "v,a=l\\ue".to_owned()));
+        point.add_field("f i=e,ld", 1.0);
+        assert_eq!(
+            render(&point),
+            r"m\ e\,a=s,k\=e\ y=v\,a\=l\\ue f\ i\=e\,ld=1 1337"
+        );
+    }
+
+    #[test]
+    fn render_strips_newlines() {
+        let mut point = Point::new("meas\nurement", 1337);
+        point
+            .tags
+            .push(("topic".to_owned(), "foo\r\nbar".to_owned()));
+        point.add_field("value", 42.0);
+        assert_eq!(render(&point), "measurement,topic=foobar value=42 1337");
+    }
+
+    #[test]
+    fn render_skips_empty_tags() {
+        let mut point = Point::new("measurement", 1337);
+        point.tags.push(("topic1".to_owned(), "a".to_owned()));
+        point.tags.push(("topic2".to_owned(), String::new()));
+        point.tags.push(("topic3".to_owned(), "\n".to_owned()));
+        point.tags.push((String::new(), "b".to_owned()));
+        point.add_field("value", 42.0);
+        assert_eq!(render(&point), "measurement,topic1=a value=42 1337");
+    }
+
+    #[test]
+    fn render_field_types() {
+        let mut point = Point::new("measurement", 1337);
+        point.fields = vec![
+            ("float".to_owned(), FieldValue::Float(1.5)),
+            ("int".to_owned(), FieldValue::Integer(-42)),
+            ("uint".to_owned(), FieldValue::UInteger(42)),
+            (
+                "string".to_owned(),
+                FieldValue::String(r#"say "hi" \o/"#.to_owned()),
+            ),
+            ("bool".to_owned(), FieldValue::Boolean(true)),
+        ];
+        assert_eq!(
+            render(&point),
+            r#"measurement float=1.5,int=-42i,uint=42u,string="say \"hi\" \\o/",bool=true 1337"#
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(
+        "weather,location=us-midwest temperature=82 1465839830100400200",
+        "weather,location=us-midwest temperature=82 1465839830100400200"
+    )]
+    #[case(
+        "weather temperature=82i,raining=f",
+        "weather temperature=82i,raining=false 1337"
+    )]
+    #[case(
+        r#"weather,station=a\ b note="hello, world" 42"#,
+        r#"weather,station=a\ b note="hello, world" 42"#
+    )]
+    fn parse_works(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(render(&parse(line, 1337).unwrap()), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::no_fields("weather")]
+    #[case::no_fields_with_tags("weather,location=us")]
+    #[case::empty_measurement(",location=us temperature=82")]
+    #[case::empty_tag_value("weather,location= temperature=82")]
+    #[case::invalid_value("weather temperature=hot")]
+    #[case::infinite("weather temperature=inf")]
+    #[case::unterminated_string(r#"weather note="hello"#)]
+    #[case::invalid_timestamp("weather temperature=82 yesterday")]
+    fn parse_invalid_fails(#[case] line: &str) {
+        assert!(parse(line, 1337).is_err());
+    }
+
+    fn without_newlines(input: &str) -> String {
+        input.replace(['\n', '\r'], "")
+    }
+
+    fn field_value() -> impl Strategy<Value = FieldValue> {
+        prop_oneof![
+            (proptest::num::f64::NORMAL | proptest::num::f64::ZERO).prop_map(FieldValue::Float),
+            any::<i64>().prop_map(FieldValue::Integer),
+            any::<u64>().prop_map(FieldValue::UInteger),
+            "[^\n\r]*".prop_map(FieldValue::String),
+            any::<bool>().prop_map(FieldValue::Boolean),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn roundtrip(
+            measurement in "[^\n\r].*",
+            tags in proptest::collection::vec((".*", ".*"), 0..5),
+            fields in proptest::collection::vec((".+", field_value()), 1..5),
+            timestamp: u64,
+        ) {
+            let mut point = Point::new(measurement, u128::from(timestamp));
+            point.tags = tags;
+            point.fields = fields;
+            prop_assume!(point.fields.iter().all(|(key, _)| !without_newlines(key).is_empty()));
+
+            let line = render(&point);
+            let parsed = parse(&line, 0).unwrap();
+
+            prop_assert_eq!(parsed.measurement, without_newlines(&point.measurement));
+            let expected_tags = point
+                .tags
+                .iter()
+                .map(|(key, value)| (without_newlines(key), without_newlines(value)))
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+                .collect::<Vec<_>>();
+            prop_assert_eq!(parsed.tags, expected_tags);
+            let expected_fields = point
+                .fields
+                .iter()
+                .map(|(key, value)| (without_newlines(key), value.clone()))
+                .collect::<Vec<_>>();
+            prop_assert_eq!(parsed.fields, expected_fields);
+            prop_assert_eq!(parsed.timestamp, point.timestamp);
+        }
+    }
+}
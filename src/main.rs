@@ -1,41 +1,104 @@
+use std::path::Path;
 use std::time::Duration;
 
-use clap::Parser as _;
+use clap::{CommandFactory as _, Parser as _};
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::time::sleep;
 
+use crate::cli::Cli;
+use crate::message::Message;
+use crate::output::Output;
+use crate::pipeline::Pipeline;
+use crate::rules::Rules;
+
+mod aggregate;
 mod cli;
+mod deadband;
+mod dedup;
 mod exit_handler;
+mod extract;
 mod floatify;
 mod influxdb;
+mod line_protocol;
 mod message;
 mod mqtt;
+mod output;
 mod payload;
+mod pipeline;
+mod point;
+mod protobuf;
+mod rate;
+mod record;
+mod replay;
+mod rules;
+mod script;
+mod sparkplug;
+#[cfg(test)]
+mod testing;
+mod text;
+mod timestamp_guard;
+mod topic_filter;
+mod transform;
+mod units;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let matches = cli::Cli::parse();
-
-    let mut influxdb = influxdb::Influxdb::new(
-        matches.influx_host,
-        matches.influx_token.as_deref(),
-        matches.influx_database.as_deref(),
-        matches.influx_org.as_deref(),
-        matches.influx_bucket.as_deref(),
-        Duration::from_secs_f32(matches.buffer_seconds),
-        matches.buffer_amount,
-        matches.verbose,
-    )
-    .await;
-    eprintln!("InfluxDB connected: {}", influxdb.get_write_url());
+    let matches = Cli::parse();
+    if matches.output == cli::Output::Influxdb
+        && matches.influx_org.is_none()
+        && matches.influx_database.is_none()
+        && !matches.victoria_metrics
+    {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "one of --influx-org, --influx-database or --victoria-metrics is required for --output influxdb",
+            )
+            .exit();
+    }
+
+    let rules = matches
+        .config
+        .as_deref()
+        .map(Rules::load)
+        .transpose()
+        .expect("failed to load config")
+        .unwrap_or_default();
+    let mut pipeline = Pipeline::new(rules, matches.verbose);
 
-    let mqtt_broker = matches.mqtt_broker;
+    let mut output = Output::new(&matches).await;
+
+    let success = match &matches.command {
+        Some(cli::Command::Replay { file }) => {
+            replay(&matches, &mut pipeline, &mut output, file).await
+        }
+        None => mqtt(&matches, &mut pipeline, &mut output).await,
+    };
+    let timestamp_outcomes = pipeline.timestamp_outcomes();
+    if !timestamp_outcomes.is_empty() {
+        eprintln!("Timestamps outside of the accepted window: {timestamp_outcomes}");
+    }
+    output.async_drop().await;
+
+    if !success {
+        std::process::exit(-1);
+    }
+}
+
+async fn mqtt(matches: &Cli, pipeline: &mut Pipeline, output: &mut Output) -> bool {
+    let recorder = matches.record.as_ref().map(|path| {
+        record::Recorder::new(path.clone(), matches.record_max_bytes, matches.record_keep)
+            .expect("failed to start recording")
+    });
+
+    let mqtt_broker = &matches.mqtt_broker;
     let (client, mut receiver) = mqtt::connect(
-        &mqtt_broker,
+        mqtt_broker,
         matches.mqtt_port,
         matches.mqtt_user.as_deref(),
         matches.mqtt_password.as_deref(),
-        matches.mqtt_topics,
+        matches.mqtt_topics.clone(),
+        recorder,
         matches.verbose,
     )
     .await;
@@ -45,7 +108,7 @@ async fn main() {
 
     eprintln!("Startup done. Listening to topics now…");
 
-    let mut error = false;
+    let mut success = true;
     loop {
         if quit.is_exiting() {
             client
@@ -56,23 +119,133 @@ async fn main() {
         }
 
         match receiver.try_recv() {
-            Ok(message) => influxdb.append(message.into_line_protocol()),
-            Err(TryRecvError::Empty) => sleep(Duration::from_millis(50)).await,
+            Ok(message) => handle(matches, pipeline, output, message),
+            Err(TryRecvError::Empty) => {
+                output.append(pipeline.flush(point::nanos_now()));
+                sleep(Duration::from_millis(50)).await;
+            }
             Err(TryRecvError::Disconnected) => {
                 eprintln!("MQTT sender is gone");
-                error = true;
+                success = false;
                 break;
             }
         }
-        influxdb.do_loop().await;
+        output.do_loop().await;
     }
 
     while let Some(message) = receiver.recv().await {
-        influxdb.append(message.into_line_protocol());
+        handle(matches, pipeline, output, message);
     }
-    influxdb.async_drop().await;
+    output.append(pipeline.flush(u128::MAX));
+    success
+}
 
-    if error {
-        std::process::exit(-1);
+async fn replay(matches: &Cli, pipeline: &mut Pipeline, output: &mut Output, file: &Path) -> bool {
+    let messages = match replay::read(file) {
+        Ok(messages) => messages,
+        Err(err) => {
+            eprintln!("Replay of {} failed: {err:#}", file.display());
+            return false;
+        }
+    };
+    let quit = exit_handler::ExitHandler::new();
+    let mut amount: usize = 0;
+    for message in messages {
+        if quit.is_exiting() {
+            break;
+        }
+        match message {
+            Ok(message) => {
+                amount = amount.saturating_add(1);
+                handle(matches, pipeline, output, message);
+            }
+            Err(err) => eprintln!("Replay skipped {err:#}"),
+        }
+        output.do_loop().await;
+    }
+    output.append(pipeline.flush(u128::MAX));
+    eprintln!("Replayed {amount} messages from {}", file.display());
+    true
+}
+
+fn handle(matches: &Cli, pipeline: &mut Pipeline, output: &mut Output, message: Message) {
+    let topic = message.topic();
+    let filters = matches
+        .mqtt_topics
+        .iter()
+        .filter(|filter| topic_filter::matches(filter, topic))
+        .collect::<Vec<_>>();
+    if filters.is_empty() {
+        if matches.verbose {
+            eprintln!("MQTT {topic} matched no topic filter, skipping");
+        }
+        return;
+    }
+    if let Some(reason) = topic_filter_skip_reason(matches, topic) {
+        if matches.verbose {
+            eprintln!("MQTT {topic} {reason}, skipping");
+        }
+        return;
     }
+    if matches.verbose {
+        match pipeline.rules().find(topic) {
+            Some(rule) => eprintln!("MQTT {topic} matched {filters:?} and rule {:?}", rule.topic),
+            None => eprintln!("MQTT {topic} matched {filters:?} and no rule"),
+        }
+    }
+    let timestamp_outcomes = pipeline.timestamp_outcomes();
+    output.append(pipeline.process(message));
+    if matches.verbose && pipeline.timestamp_outcomes() != timestamp_outcomes {
+        eprintln!(
+            "Timestamps outside of the accepted window: {}",
+            pipeline.timestamp_outcomes()
+        );
+    }
+}
+
+/// Include and exclude filters applied in addition to the MQTT subscription
+fn topic_filter_skip_reason(matches: &Cli, topic: &str) -> Option<String> {
+    if !matches.include_topic_regex.is_empty()
+        && !matches
+            .include_topic_regex
+            .iter()
+            .any(|regex| regex.is_match(topic))
+    {
+        return Some("matched no include regex".to_owned());
+    }
+    if let Some(filter) = matches
+        .exclude_topic
+        .iter()
+        .find(|filter| topic_filter::matches(filter, topic))
+    {
+        return Some(format!("matched exclude {filter:?}"));
+    }
+    if let Some(regex) = matches
+        .exclude_topic_regex
+        .iter()
+        .find(|regex| regex.is_match(topic))
+    {
+        return Some(format!("matched exclude regex {:?}", regex.as_str()));
+    }
+    None
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::no_filter(&[], "foo/bar", None)]
+#[case::include(&["--include-topic-regex", "^foo/"], "foo/bar", None)]
+#[case::not_included(&["--include-topic-regex", "^foo/"], "bar/foo", Some("matched no include regex"))]
+#[case::exclude(&["--exclude-topic", "+/+/set"], "foo/bar/set", Some(r#"matched exclude "+/+/set""#))]
+#[case::not_excluded(&["--exclude-topic", "+/+/set"], "foo/set", None)]
+#[case::exclude_regex(&["--exclude-topic-regex", "^zigbee2mqtt/bridge"], "zigbee2mqtt/bridge/logging", Some(r#"matched exclude regex "^zigbee2mqtt/bridge""#))]
+fn topic_filter_skip_reason_works(
+    #[case] args: &[&str],
+    #[case] topic: &str,
+    #[case] expected: Option<&str>,
+) {
+    let matches = Cli::parse_from(["mqtt2influxdb", "--output=stdout"].iter().chain(args));
+    assert_eq!(
+        topic_filter_skip_reason(&matches, topic).as_deref(),
+        expected
+    );
 }
@@ -1,7 +1,11 @@
-use std::fmt::Write as _;
+use std::fmt::Display;
 
-use crate::payload::{Payload, Values};
+use crate::line_protocol;
+use crate::payload::{self, Format, Key, Number, Payload, Values};
+use crate::point::Point;
+use crate::rules::Rule;
 
+#[derive(Debug, PartialEq, Eq)]
 pub struct Message {
     nanos: u128,
     topic: String,
@@ -17,40 +21,114 @@ impl Message {
         }
     }
 
-    /// Output as [Line Protocol](https://docs.influxdata.com/influxdb/v2.1/reference/syntax/line-protocol/)
-    pub fn into_line_protocol(self) -> Vec<String> {
-        let Some(payload) = Payload::new(self.payload) else {
+    pub const fn nanos(&self) -> u128 {
+        self.nanos
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// With `verbose` skipped parts of the payload are explained
+    pub fn into_points(self, rule: &Rule, verbose: bool) -> Vec<Point> {
+        let topic_tags = if rule.topic_tags() {
+            topic_tags(&self.topic)
+        } else {
+            Vec::new()
+        };
+        if matches!(rule.format, Format::LineProtocol) {
+            return line_protocol_points(&self, &topic_tags, verbose);
+        }
+        let Some(payload) = Payload::new(self.payload, &rule.format, rule.numbers) else {
             return Vec::new();
         };
-        let Some(values) = Values::from(&payload) else {
+        let nanos = self.nanos;
+        if let Some(extract) = &rule.extract {
+            let Some(json) = payload.as_json() else {
+                return Vec::new();
+            };
+            let mut point = Point::new("measurement", nanos);
+            point.tags = topic_tags;
+            point.tags.extend(extract.tags(&json));
+            for (key, value) in extract.fields(&json, rule.floatify_options()) {
+                if let Some(value) = rule.transform.apply(&key, value) {
+                    point.add_field(key, value);
+                }
+            }
+            if point.fields.is_empty() {
+                return Vec::new();
+            }
+            return vec![point];
+        }
+        let options = payload::Options {
+            array_id: rule.keys.array_id.as_deref(),
+            floatify: rule.floatify_options(),
+        };
+        let Some(values) = Values::from(&payload, options) else {
             return Vec::new();
         };
-        let topic_tags = topic_tags(&self.topic);
-        let Self { nanos, .. } = self;
-        match values {
-            Values::Single(value) => {
-                vec![format!(
-                    "measurement,{topic_tags},keySegments=0 value={value} {nanos}"
-                )]
+        let value_tags = value_tags(rule, &payload);
+        let point = |path: &str, key_tags: Vec<(String, String)>, number: Number<'_>| {
+            let (value, unit) = rule
+                .units
+                .apply(path, number.raw, number.value, rule.numbers)?;
+            let value = rule.transform.apply(path, value)?;
+            let mut point = Point::new("measurement", nanos);
+            point.tags.clone_from(&topic_tags);
+            point.tags.extend(value_tags.iter().cloned());
+            point.tags.extend(key_tags);
+            if let Some(unit) = unit {
+                point.tags.push(("unit".to_owned(), unit.to_string()));
             }
+            point.add_field("value", value);
+            Some(point)
+        };
+        match values {
+            Values::Single(number) => point("", key_tags::<Key>(&[]), number)
+                .into_iter()
+                .collect(),
             Values::Many(many) => many
                 .into_iter()
-                .map(|(keys, value)| {
-                    let key_tags = key_tags(&keys);
-                    format!("measurement,{topic_tags},{key_tags} value={value} {nanos}")
+                .filter_map(|(keys, number)| {
+                    let path = keys
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(".");
+                    if !rule.keys.is_selected(&path) || rule.keys.tags.contains(&path) {
+                        return None;
+                    }
+                    let key_tags = rule.keys.renamed(&path).map_or_else(
+                        || key_tags(&keys),
+                        |renamed| key_tags(&renamed.split('.').collect::<Vec<_>>()),
+                    );
+                    point(&path, key_tags, number)
                 })
                 .collect(),
         }
     }
 }
 
+#[cfg(test)]
+fn render_lines(message: Message, rule: &Rule) -> Vec<String> {
+    message
+        .into_points(rule, false)
+        .iter()
+        .map(line_protocol::render)
+        .collect()
+}
+
 #[cfg(test)]
 #[rstest::rstest]
 #[case::plain_number(b"42", &["measurement,topic=foo/bar,topic1=foo,topic2=bar,topicE1=bar,topicE2=foo,topicSegments=2,keySegments=0 value=42 1337"])]
 #[case::string(b"whatever", &[])]
 fn e2e(#[case] payload: &[u8], #[case] expected: &[&str]) {
     let message = Message::new(1337, "foo/bar".into(), payload.to_vec());
-    assert_eq!(message.into_line_protocol(), expected);
+    assert_eq!(render_lines(message, &Rule::default()), expected);
 }
 
 #[test]
@@ -61,61 +139,410 @@ fn e2e_json() {
         "measurement,topic=foo/bar,topic1=foo,topic2=bar,topicE1=bar,topicE2=foo,topicSegments=2,key1=a,keySegments=1 value=42 1337",
         "measurement,topic=foo/bar,topic1=foo,topic2=bar,topicE1=bar,topicE2=foo,topicSegments=2,key1=b,key2=c,keySegments=2 value=666 1337",
     ];
-    assert_eq!(message.into_line_protocol(), expected);
+    assert_eq!(render_lines(message, &Rule::default()), expected);
+}
+
+#[test]
+fn e2e_json_keys() {
+    let payload = serde_json::to_vec(&serde_json::json!({
+        "temperature_c": 21.5,
+        "linkquality": 42,
+        "update": {"state": 1, "progress": 50},
+    }))
+    .unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        keys.exclude = ["linkquality", "update.*"]
+        keys.rename = { temperature_c = "climate.temperature" }
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "measurement,topic=foo,topic1=foo,topicE1=foo,topicSegments=1,key1=climate,key2=temperature,keySegments=2 value=21.5 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
 }
 
-/// Influx Line Protocol Escape
-fn line_protocol_escape(str: &str) -> String {
-    str.replace(' ', "\\ ").replace(',', "\\,")
+#[test]
+fn e2e_value_tags() {
+    let payload =
+        serde_json::to_vec(&serde_json::json!({"state": "heat", "temp": 21.5, "valve": 42}))
+            .unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        keys.tags = ["state", "valve"]
+        keys.rename = { state = "mode" }
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "measurement,topic=foo,topic1=foo,topicE1=foo,topicSegments=1,mode=heat,valve=42,key1=temp,keySegments=1 value=21.5 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
 }
 
-fn topic_tags(topic: &str) -> String {
-    let topic = line_protocol_escape(topic);
+#[test]
+fn e2e_value_tags_dotted_key() {
+    let payload = serde_json::to_vec(
+        &serde_json::json!({"dev.state": "on", "dev": {"room": "kitchen"}, "temp": 21.5}),
+    )
+    .unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        topic_tags = false
+        keys.tags = ["dev.state", "dev.room"]
+        "#,
+    )
+    .unwrap();
+    let expected =
+        ["measurement,dev.state=on,dev.room=kitchen,key1=temp,keySegments=1 value=21.5 1337"];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[test]
+fn e2e_array_id() {
+    let payload =
+        serde_json::to_vec(&serde_json::json!({"sensors": [{"id": "28-abc", "t": 20.1}]})).unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        keys.array_id = "id"
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "measurement,topic=foo,topic1=foo,topicE1=foo,topicSegments=1,key1=sensors,key2=28-abc,key3=t,keySegments=3 value=20.1 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[test]
+fn e2e_extract() {
+    let payload = serde_json::to_vec(&serde_json::json!({
+        "data": {"meter": {"id": "abc", "power": 42, "total": 1337.5}, "other": 666},
+    }))
+    .unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        extract.fields = { power = "$.data.meter.power", energy = "$.data.meter.total" }
+        extract.tags = { meter = "$.data.meter.id" }
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "measurement,topic=foo,topic1=foo,topicE1=foo,topicSegments=1,meter=abc energy=1337.5,power=42 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::key_value(r#"format = "key-value""#, "state=heat t=21.3 h=40")]
+#[case::key_value_decimal_comma(
+    "format = \"key-value\"\nnumbers = { decimal = \",\" }",
+    "state=heat t=21,3 h=40"
+)]
+#[case::csv(
+    r#"format.csv = { columns = ["state", "t", "h"], delimiter = ";" }"#,
+    "heat;21.3;40"
+)]
+fn e2e_text(#[case] format: &str, #[case] payload: &str) {
+    let message = Message::new(1337, "foo".into(), payload.into());
+    let rule = toml::from_str::<Rule>(&format!(
+        "topic = \"foo\"\nkeys.tags = [\"state\"]\n{format}"
+    ))
+    .unwrap();
+    let expected = [
+        "measurement,topic=foo,topic1=foo,topicE1=foo,topicSegments=1,state=heat,key1=h,keySegments=1 value=40 1337",
+        "measurement,topic=foo,topic1=foo,topicE1=foo,topicSegments=1,state=heat,key1=t,keySegments=1 value=21.3 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[test]
+fn e2e_line_protocol() {
+    let payload =
+        "# comment\ncpu,host=a usage=0.5,cores=8i 42\n\ncpu,host=b usage=0.25\nnot valid\n";
+    let message = Message::new(1337, "telegraf/cpu".into(), payload.into());
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "telegraf/#"
+        format = "line-protocol"
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "cpu,host=a usage=0.5,cores=8i 42",
+        "cpu,host=b usage=0.25 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[test]
+fn e2e_line_protocol_topic_tags() {
+    let message = Message::new(1337, "telegraf/cpu".into(), b"cpu usage=0.5 42".to_vec());
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "telegraf/#"
+        format = "line-protocol"
+        topic_tags = true
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "cpu,topic=telegraf/cpu,topic1=telegraf,topic2=cpu,topicE1=cpu,topicE2=telegraf,topicSegments=2 usage=0.5 42",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[test]
+fn e2e_units() {
+    let payload = serde_json::to_vec(&serde_json::json!({
+        "power": "1.2 kW",
+        "voltage": "230 V",
+        "current": "5 W",
+        "energy": 42,
+    }))
+    .unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        topic_tags = false
+        units.canonical = { power = "W", current = "A", energy = "kWh" }
+        units.tag = true
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "measurement,key1=energy,keySegments=1,unit=kWh value=42 1337",
+        "measurement,key1=power,keySegments=1,unit=W value=1200 1337",
+        "measurement,key1=voltage,keySegments=1,unit=V value=230 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::array_id(
+    serde_json::json!({"s": [{"id": "a", "p": "1.2 kW"}]}),
+    "measurement,key1=s,key2=a,key3=p,keySegments=3 value=1200 1337"
+)]
+#[case::dotted_key(
+    serde_json::json!({"a.b": "1.2 kW"}),
+    "measurement,key1=a.b,keySegments=1 value=1200 1337"
+)]
+fn e2e_units_raw_string(#[case] payload: serde_json::Value, #[case] expected: &str) {
+    let message = Message::new(1337, "foo".into(), serde_json::to_vec(&payload).unwrap());
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        topic_tags = false
+        keys.array_id = "id"
+        units.canonical = { "*" = "W" }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(render_lines(message, &rule), [expected]);
+}
+
+#[test]
+fn e2e_transform() {
+    let payload =
+        serde_json::to_vec(&serde_json::json!({"adc": 650, "temperature": 215, "other": 42}))
+            .unwrap();
+    let message = Message::new(1337, "foo".into(), payload);
+    let rule = toml::from_str::<Rule>(
+        r#"
+        topic = "foo"
+        topic_tags = false
+        transform.adc = { scale = 0.5, offset = -40 }
+        transform.temperature = "value / 10"
+        "#,
+    )
+    .unwrap();
+    let expected = [
+        "measurement,key1=adc,keySegments=1 value=285 1337",
+        "measurement,key1=other,keySegments=1 value=42 1337",
+        "measurement,key1=temperature,keySegments=1 value=21.5 1337",
+    ];
+    assert_eq!(render_lines(message, &rule), expected);
+}
+
+#[test]
+fn e2e_escaping() {
+    let payload = serde_json::to_vec(&serde_json::json!({"a b=c": 42})).unwrap();
+    let message = Message::new(1337, "foo bar//b,az".into(), payload);
+    let expected = [
+        r"measurement,topic=foo\ bar//b\,az,topic1=foo\ bar,topic3=b\,az,topicE1=b\,az,topicE3=foo\ bar,topicSegments=3,key1=a\ b\=c,keySegments=1 value=42 1337",
+    ];
+    assert_eq!(render_lines(message, &Rule::default()), expected);
+}
+
+fn topic_tags(topic: &str) -> Vec<(String, String)> {
     let parts = topic.split('/').collect::<Vec<_>>();
-    let mut tags = format!("topic={topic},");
+    let mut tags = vec![("topic".to_owned(), topic.to_owned())];
     for (i, part) in parts.iter().enumerate() {
-        _ = write!(&mut tags, "topic{}={part},", i + 1);
+        tags.push((format!("topic{}", i + 1), (*part).to_owned()));
     }
     for (i, part) in parts.iter().rev().take(3).enumerate() {
-        _ = write!(&mut tags, "topicE{}={part},", i + 1);
+        tags.push((format!("topicE{}", i + 1), (*part).to_owned()));
     }
-    _ = write!(&mut tags, "topicSegments={}", parts.len());
+    tags.push(("topicSegments".to_owned(), parts.len().to_string()));
     tags
 }
 
+#[cfg(test)]
+#[track_caller]
+fn assert_tags(actual: &[(String, String)], expected: &str) {
+    let actual = actual
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    assert_eq!(actual, expected);
+}
+
 #[test]
 fn topic_tags_short_works() {
-    assert_eq!(
-        topic_tags("foo/bar"),
+    assert_tags(
+        &topic_tags("foo/bar"),
         "topic=foo/bar,topic1=foo,topic2=bar,topicE1=bar,topicE2=foo,topicSegments=2",
     );
 }
 
 #[test]
 fn topic_tags_long_works() {
-    assert_eq!(
-        topic_tags("base/foo/bar/test"),
+    assert_tags(
+        &topic_tags("base/foo/bar/test"),
         "topic=base/foo/bar/test,topic1=base,topic2=foo,topic3=bar,topic4=test,topicE1=test,topicE2=bar,topicE3=foo,topicSegments=4",
     );
 }
 
-fn key_tags(keys: &[crate::payload::Key<'_>]) -> String {
-    let mut tags = String::new();
-    for (index, key) in keys.iter().enumerate() {
-        if index > 0 {
-            _ = write!(tags, ",");
-        }
-        _ = write!(tags, "key{}={key}", index.saturating_add(1));
+/// Forward valid lines of line protocol with the topic tags appended to their tags
+fn line_protocol_points(
+    message: &Message,
+    topic_tags: &[(String, String)],
+    verbose: bool,
+) -> Vec<Point> {
+    let Ok(payload) = std::str::from_utf8(&message.payload) else {
+        return Vec::new();
+    };
+    payload
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line_protocol::parse(line, message.nanos) {
+            Ok(mut point) => {
+                point.tags.extend_from_slice(topic_tags);
+                Some(point)
+            }
+            Err(err) => {
+                if verbose {
+                    eprintln!(
+                        "MQTT {} skipped invalid line protocol {line:?}: {err:#}",
+                        message.topic
+                    );
+                }
+                None
+            }
+        })
+        .collect()
+}
+
+/// Values of the payload which are tags for all the other values of the payload
+fn value_tags(rule: &Rule, payload: &Payload) -> Vec<(String, String)> {
+    if rule.keys.tags.is_empty() {
+        return Vec::new();
+    }
+    let Some(json) = payload.as_json() else {
+        return Vec::new();
+    };
+    rule.keys
+        .tags
+        .iter()
+        .filter_map(|path| {
+            let value = match lookup(&json, path)? {
+                serde_json::Value::String(string) => string.clone(),
+                serde_json::Value::Number(number) => number.to_string(),
+                serde_json::Value::Bool(bool) => bool.to_string(),
+                serde_json::Value::Null
+                | serde_json::Value::Array(_)
+                | serde_json::Value::Object(_) => return None,
+            };
+            let key = rule.keys.renamed(path).unwrap_or(path);
+            Some((key.to_owned(), value))
+        })
+        .collect()
+}
+
+/// Value at a key path like `foo.bar` by its segments. Keys may contain `.` themselves like `{"foo.bar": 42}`.
+fn lookup<'j>(value: &'j serde_json::Value, path: &str) -> Option<&'j serde_json::Value> {
+    let child = |key: &str| match value {
+        serde_json::Value::Object(object) => object.get(key),
+        serde_json::Value::Array(array) => array.get(key.parse::<usize>().ok()?),
+        _ => None,
+    };
+    if let Some(found) = child(path) {
+        return Some(found);
     }
-    assert!(!keys.is_empty()); // start with a ,
-    _ = write!(tags, ",keySegments={}", keys.len());
+    path.match_indices('.')
+        .find_map(|(index, _)| lookup(child(&path[..index])?, &path[index + 1..]))
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::nested("a.b", Some(1))]
+#[case::dotted_key("c.d", Some(2))]
+#[case::dotted_and_nested("e.f.g", Some(3))]
+#[case::array("h.1", Some(4))]
+#[case::missing("a.c", None)]
+fn lookup_works(#[case] path: &str, #[case] expected: Option<u64>) {
+    let json = serde_json::json!({"a": {"b": 1}, "c.d": 2, "e.f": {"g": 3}, "h": [0, 4]});
+    assert_eq!(
+        lookup(&json, path).and_then(serde_json::Value::as_u64),
+        expected
+    );
+}
+
+fn key_tags<K: Display>(keys: &[K]) -> Vec<(String, String)> {
+    let mut tags = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| (format!("key{}", index.saturating_add(1)), key.to_string()))
+        .collect::<Vec<_>>();
+    tags.push(("keySegments".to_owned(), keys.len().to_string()));
     tags
 }
 
+/// Payload key path like `meter.total` of points of payload values by their `key1`, `key2`, … tags
+pub fn key_path(point: &Point) -> Option<String> {
+    let tag = |name: &str| {
+        point
+            .tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let segments = tag("keySegments")?.parse::<usize>().ok()?;
+    let keys = (1..=segments)
+        .map(|index| tag(&format!("key{index}")))
+        .collect::<Option<Vec<_>>>()?;
+    Some(keys.join("."))
+}
+
 #[test]
 fn key_tags_works() {
-    use crate::payload::Key;
     let keys = [Key::String("foo"), Key::String("bar"), Key::Int(42)];
     let result = key_tags(&keys);
-    assert_eq!(result, "key1=foo,key2=bar,key3=42,keySegments=3");
+    assert_tags(&result, "key1=foo,key2=bar,key3=42,keySegments=3");
 }
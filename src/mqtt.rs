@@ -1,4 +1,4 @@
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, Packet, QoS};
 use tokio::sync::mpsc::{Receiver, channel};
@@ -6,6 +6,8 @@ use tokio::task;
 use tokio::time::sleep;
 
 use crate::message::Message;
+use crate::point;
+use crate::record::Recorder;
 
 pub async fn connect(
     broker: &str,
@@ -13,6 +15,7 @@ pub async fn connect(
     username: Option<&str>,
     password: Option<&str>,
     topics: Vec<String>,
+    mut recorder: Option<Recorder>,
     verbose: bool,
 ) -> (AsyncClient, Receiver<Message>) {
     let client_id = format!("mqtt2influxdb-{:x}", rand::random::<u32>());
@@ -42,11 +45,11 @@ pub async fn connect(
         loop {
             let event = eventloop.poll().await;
             if verbose {
-                println!("MQTT Event {event:?}");
+                eprintln!("MQTT Event {event:?}");
             }
             match event {
                 Ok(Event::Incoming(Packet::ConnAck(packet))) => {
-                    println!("MQTT connected {packet:?}");
+                    eprintln!("MQTT connected {packet:?}");
                     if !packet.session_present {
                         subscribe(&client, topics.clone())
                             .await
@@ -54,22 +57,36 @@ pub async fn connect(
                     }
                 }
                 Ok(Event::Outgoing(Outgoing::Disconnect)) => {
-                    println!("MQTT Disconnect happening...");
+                    eprintln!("MQTT Disconnect happening...");
                     break;
                 }
-                Ok(Event::Incoming(Packet::Publish(packet)))
-                    if !packet.dup && !packet.retain && !packet.payload.is_empty() =>
-                {
-                    let nanos = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_nanos();
-                    let message = Message::new(nanos, packet.topic, packet.payload.into());
-                    sender.send(message).await.expect("receiver died");
+                Ok(Event::Incoming(Packet::Publish(packet))) => {
+                    let nanos = point::nanos_now();
+                    if let Some(recorder) = &mut recorder {
+                        let qos = match packet.qos {
+                            QoS::AtMostOnce => 0,
+                            QoS::AtLeastOnce => 1,
+                            QoS::ExactlyOnce => 2,
+                        };
+                        if let Err(err) = recorder.record(
+                            nanos,
+                            &packet.topic,
+                            qos,
+                            packet.dup,
+                            packet.retain,
+                            &packet.payload,
+                        ) {
+                            eprintln!("MQTT record failed: {err:#}");
+                        }
+                    }
+                    if !packet.dup && !packet.retain && !packet.payload.is_empty() {
+                        let message = Message::new(nanos, packet.topic, packet.payload.into());
+                        sender.send(message).await.expect("receiver died");
+                    }
                 }
                 Ok(_) => {}
                 Err(err) => {
-                    println!("MQTT Connection Error: {err}");
+                    eprintln!("MQTT Connection Error: {err}");
                     sleep(Duration::from_secs(1)).await;
                 }
             }
@@ -0,0 +1,75 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::cli::{self, Cli};
+use crate::influxdb::Influxdb;
+use crate::line_protocol;
+use crate::point::Point;
+
+pub enum Output {
+    Influxdb(Influxdb),
+    /// Write the line protocol instead of sending it somewhere
+    Lines(Box<dyn Write + Send>),
+}
+
+impl Output {
+    pub async fn new(matches: &Cli) -> Self {
+        match &matches.output {
+            cli::Output::Influxdb => {
+                let influxdb = Influxdb::new(
+                    matches.influx_host.clone(),
+                    matches.influx_token.as_deref(),
+                    matches.influx_database.as_deref(),
+                    matches.influx_org.as_deref(),
+                    matches.influx_bucket.as_deref(),
+                    Duration::from_secs_f32(matches.buffer_seconds),
+                    matches.buffer_amount,
+                    matches.verbose,
+                )
+                .await;
+                eprintln!("InfluxDB connected: {}", influxdb.get_write_url());
+                Self::Influxdb(influxdb)
+            }
+            cli::Output::Stdout => Self::Lines(Box::new(std::io::stdout())),
+            cli::Output::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .expect("failed to open output file");
+                eprintln!("Output file opened: {}", path.display());
+                Self::Lines(Box::new(file))
+            }
+        }
+    }
+
+    /// Append to the points that will be written
+    pub fn append(&mut self, points: Vec<Point>) {
+        match self {
+            Self::Influxdb(influxdb) => influxdb.append(points),
+            Self::Lines(writer) => {
+                for point in points {
+                    writeln!(writer, "{}", line_protocol::render(&point))
+                        .expect("failed to write output");
+                }
+                writer.flush().expect("failed to flush output");
+            }
+        }
+    }
+
+    pub async fn do_loop(&mut self) {
+        match self {
+            Self::Influxdb(influxdb) => influxdb.do_loop().await,
+            Self::Lines(_) => {}
+        }
+    }
+
+    /// This is a workaround as `impl Drop` can't do something async
+    pub async fn async_drop(&mut self) {
+        match self {
+            Self::Influxdb(influxdb) => influxdb.async_drop().await,
+            Self::Lines(writer) => writer.flush().expect("failed to flush output"),
+        }
+    }
+}
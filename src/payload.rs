@@ -1,25 +1,135 @@
-use crate::floatify::floatify;
+use std::borrow::Cow;
+
+use serde::Deserialize;
+
+use crate::floatify::{self, Numbers, floatify};
+use crate::protobuf::Protobuf;
+use crate::text::{self, Csv};
+
+/// Decoder of the payload
+#[expect(clippy::doc_markdown)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// UTF-8 is parsed as JSON or a plain string, everything else as MessagePack
+    #[default]
+    Auto,
+    String,
+    Json,
+    MessagePack,
+    Cbor,
+    /// Decoded into JSON with the given message type
+    Protobuf(Protobuf),
+    /// `key=value` pairs like `t=21.3 h=40 p=1013`
+    #[serde(rename = "key-value")]
+    KeyValue,
+    /// Values like `21.3;40;1013` with the given column names
+    Csv(Csv),
+    /// Lines of InfluxDB line protocol which are forwarded as they are
+    #[serde(rename = "line-protocol")]
+    LineProtocol,
+    /// Sparkplug B messages need the state of previous messages and are handled by [`crate::sparkplug::Sparkplug`]
+    Sparkplug,
+}
 
 #[derive(Debug)]
 pub enum Payload {
     String(String),
     Json(serde_json::Value),
     MessagePack(rmpv::Value),
+    Cbor(ciborium::Value),
 }
 
 impl Payload {
-    pub fn new(payload: Vec<u8>) -> Option<Self> {
-        match String::from_utf8(payload) {
-            Ok(payload) => {
-                Some(serde_json::from_str(&payload).map_or(Self::String(payload), Self::Json))
-            }
-            Err(err) => rmpv::decode::read_value(&mut err.as_bytes())
+    /// `numbers` keeps decimal and thousands separators within the values of `key=value` pairs
+    pub fn new(payload: Vec<u8>, format: &Format, numbers: Numbers) -> Option<Self> {
+        match format {
+            Format::Auto => match String::from_utf8(payload) {
+                Ok(payload) => {
+                    Some(serde_json::from_str(&payload).map_or(Self::String(payload), Self::Json))
+                }
+                Err(err) => rmpv::decode::read_value(&mut err.as_bytes())
+                    .ok()
+                    .map(Self::MessagePack),
+            },
+            Format::String => String::from_utf8(payload).ok().map(Self::String),
+            Format::Json => serde_json::from_slice(&payload).ok().map(Self::Json),
+            Format::MessagePack => rmpv::decode::read_value(&mut payload.as_slice())
                 .ok()
                 .map(Self::MessagePack),
+            Format::Cbor => ciborium::from_reader(payload.as_slice())
+                .ok()
+                .map(Self::Cbor),
+            Format::Protobuf(protobuf) => protobuf.decode(&payload).map(Self::Json),
+            Format::KeyValue => String::from_utf8(payload)
+                .ok()
+                .map(|payload| Self::Json(text::key_value(&payload, numbers))),
+            Format::Csv(csv) => String::from_utf8(payload)
+                .ok()
+                .map(|payload| Self::Json(csv.parse(&payload))),
+            // Not values but points, see Message::into_points and crate::sparkplug
+            Format::LineProtocol | Format::Sparkplug => None,
+        }
+    }
+
+    /// Structured payloads as JSON, `None` for plain strings
+    pub fn as_json(&self) -> Option<Cow<'_, serde_json::Value>> {
+        match self {
+            Self::String(_) => None,
+            Self::Json(value) => Some(Cow::Borrowed(value)),
+            Self::MessagePack(value) => Some(Cow::Owned(messagepack_to_json(value))),
+            Self::Cbor(value) => Some(Cow::Owned(cbor_to_json(value))),
         }
     }
 }
 
+fn cbor_to_json(value: &ciborium::Value) -> serde_json::Value {
+    use ciborium::Value;
+    match value {
+        Value::Bool(bool) => serde_json::Value::Bool(*bool),
+        Value::Integer(int) => i64::try_from(*int)
+            .map(Into::into)
+            .or_else(|_| u64::try_from(*int).map(Into::into))
+            .unwrap_or_default(),
+        Value::Float(float) => serde_json::Number::from_f64(*float).into(),
+        Value::Text(text) => text.as_str().into(),
+        Value::Tag(_, value) => cbor_to_json(value),
+        Value::Array(array) => array.iter().map(cbor_to_json).collect(),
+        Value::Map(map) => map
+            .iter()
+            .filter_map(|(key, value)| {
+                let key = Key::try_from(key).ok()?.to_string();
+                Some((key, cbor_to_json(value)))
+            })
+            .collect(),
+        _ => serde_json::Value::Null,
+    }
+}
+
+fn messagepack_to_json(value: &rmpv::Value) -> serde_json::Value {
+    use rmpv::Value;
+    match value {
+        Value::Boolean(bool) => serde_json::Value::Bool(*bool),
+        Value::Integer(int) => int
+            .as_i64()
+            .map(Into::into)
+            .or_else(|| int.as_u64().map(Into::into))
+            .unwrap_or_default(),
+        Value::F32(float) => serde_json::Number::from_f64(f64::from(*float)).into(),
+        Value::F64(float) => serde_json::Number::from_f64(*float).into(),
+        Value::String(str) => str.as_str().map(Into::into).unwrap_or_default(),
+        Value::Array(array) => array.iter().map(messagepack_to_json).collect(),
+        Value::Map(map) => map
+            .iter()
+            .filter_map(|(key, value)| {
+                let key = Key::try_from(key).ok()?.to_string();
+                Some((key, messagepack_to_json(value)))
+            })
+            .collect(),
+        Value::Nil | Value::Binary(_) | Value::Ext(_, _) => serde_json::Value::Null,
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Key<'s> {
     String(&'s str),
@@ -56,18 +166,72 @@ impl<'s> TryFrom<&'s rmpv::Value> for Key<'s> {
     }
 }
 
+impl<'s> TryFrom<&'s ciborium::Value> for Key<'s> {
+    type Error = ();
+
+    fn try_from(value: &'s ciborium::Value) -> Result<Self, Self::Error> {
+        use ciborium::Value;
+        match value {
+            Value::Bool(true) => Ok(Key::Int(1)),
+            Value::Bool(false) | Value::Null => Ok(Key::Int(0)),
+            Value::Integer(int) => usize::try_from(*int).map(Key::Int).map_err(|_| ()),
+            Value::Text(text) => Ok(Key::String(text)),
+            Value::Tag(_, value) => value.as_ref().try_into(),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Number of a payload value with the original string when it was a string like `1.2 kW`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number<'s> {
+    pub value: f64,
+    pub raw: Option<&'s str>,
+}
+
+impl From<f64> for Number<'_> {
+    fn from(value: f64) -> Self {
+        Self { value, raw: None }
+    }
+}
+
+impl<'s> Number<'s> {
+    fn parse(raw: &'s str, options: floatify::Options<'_>) -> Option<Self> {
+        Some(Self {
+            value: floatify(raw, options)?,
+            raw: Some(raw),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum Values<'k> {
-    Single(f64),
-    Many(Vec<(Vec<Key<'k>>, f64)>),
+    Single(Number<'k>),
+    Many(Vec<(Vec<Key<'k>>, Number<'k>)>),
+}
+
+/// How structured payloads are flattened into values
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Options<'o> {
+    /// Use the value of this member of objects within arrays as key instead of the array index.
+    ///
+    /// `{"sensors": [{"id": "28-abc", "t": 20.1}]}` results in the key `sensors.28-abc.t` instead of `sensors.0.t`.
+    /// Arrays with elements without this member keep the index of every element.
+    pub array_id: Option<&'o str>,
+
+    /// How string values are converted into numbers
+    pub floatify: floatify::Options<'o>,
 }
 
 impl<'k> Values<'k> {
-    pub fn from(payload: &'k Payload) -> Option<Self> {
+    pub fn from(payload: &'k Payload, options: Options<'_>) -> Option<Self> {
         let many = match payload {
-            Payload::String(payload) => return Some(Self::Single(floatify(payload)?)),
-            Payload::Json(payload) => json(payload),
-            Payload::MessagePack(payload) => messagepack(payload),
+            Payload::String(payload) => {
+                return Some(Self::Single(Number::parse(payload, options.floatify)?));
+            }
+            Payload::Json(payload) => json(payload, options),
+            Payload::MessagePack(payload) => messagepack(payload, options),
+            Payload::Cbor(payload) => cbor(payload, options),
         };
         // Cleanup many
         match many.as_slice() {
@@ -79,32 +243,60 @@ impl<'k> Values<'k> {
     }
 }
 
-pub fn json(value: &serde_json::Value) -> Vec<(Vec<Key<'_>>, f64)> {
+pub fn json<'json>(
+    value: &'json serde_json::Value,
+    options: Options<'_>,
+) -> Vec<(Vec<Key<'json>>, Number<'json>)> {
     use serde_json::Value;
+    fn array_id<'json>(value: &'json Value, id: &str) -> Option<Key<'json>> {
+        match value.as_object()?.get(id)? {
+            Value::String(string) => Some(Key::String(string)),
+            Value::Number(number) => Some(Key::Int(usize::try_from(number.as_u64()?).ok()?)),
+            _ => None,
+        }
+    }
     fn inner<'json>(
-        result: &mut Vec<(Vec<Key<'json>>, f64)>,
+        result: &mut Vec<(Vec<Key<'json>>, Number<'json>)>,
+        options: Options<'_>,
         current_key: Vec<Key<'json>>,
         value: &'json Value,
+        skip_member: Option<&str>,
     ) -> Option<()> {
         let simple = match value {
             Value::Null => None,
-            Value::Bool(true) => Some(1.0),
-            Value::Bool(false) => Some(0.0),
-            Value::Number(value) => value.as_f64(),
-            Value::String(value) => floatify(value),
+            Value::Bool(true) => Some(1.0.into()),
+            Value::Bool(false) => Some(0.0.into()),
+            Value::Number(value) => value.as_f64().map(Number::from),
+            Value::String(value) => Number::parse(value, options.floatify),
             Value::Array(array) => {
+                // Ids only when every element has one so they can not collide with indexes
+                let ids = options.array_id.and_then(|id| {
+                    let keys = array
+                        .iter()
+                        .map(|value| array_id(value, id))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some((id, keys))
+                });
                 for (index, value) in array.iter().enumerate() {
                     let mut current_key = current_key.clone();
-                    current_key.push(Key::Int(index));
-                    _ = inner(result, current_key, value);
+                    if let Some((id, keys)) = &ids {
+                        current_key.push(keys[index]);
+                        _ = inner(result, options, current_key, value, Some(id));
+                    } else {
+                        current_key.push(Key::Int(index));
+                        _ = inner(result, options, current_key, value, None);
+                    }
                 }
                 None
             }
             Value::Object(object) => {
                 for (key, value) in object {
+                    if skip_member == Some(key) {
+                        continue;
+                    }
                     let mut current_key = current_key.clone();
                     current_key.push(Key::String(key));
-                    _ = inner(result, current_key, value);
+                    _ = inner(result, options, current_key, value, None);
                 }
                 None
             }
@@ -113,37 +305,68 @@ pub fn json(value: &serde_json::Value) -> Vec<(Vec<Key<'_>>, f64)> {
         Some(())
     }
     let mut result = Vec::new();
-    _ = inner(&mut result, Vec::new(), value);
+    _ = inner(&mut result, options, Vec::new(), value, None);
     result
 }
 
-pub fn messagepack(value: &rmpv::Value) -> Vec<(Vec<Key<'_>>, f64)> {
+pub fn messagepack<'json>(
+    value: &'json rmpv::Value,
+    options: Options<'_>,
+) -> Vec<(Vec<Key<'json>>, Number<'json>)> {
     use rmpv::Value;
+    fn array_id<'json>(value: &'json Value, id: &str) -> Option<Key<'json>> {
+        let (_, value) = value
+            .as_map()?
+            .iter()
+            .find(|(key, _)| key.as_str() == Some(id))?;
+        match value {
+            Value::String(_) | Value::Integer(_) => value.try_into().ok(),
+            _ => None,
+        }
+    }
     fn inner<'json>(
-        result: &mut Vec<(Vec<Key<'json>>, f64)>,
+        result: &mut Vec<(Vec<Key<'json>>, Number<'json>)>,
+        options: Options<'_>,
         current_key: Vec<Key<'json>>,
         value: &'json Value,
+        skip_member: Option<&str>,
     ) -> Option<()> {
         let simple = match value {
-            Value::Boolean(true) => Some(1.0),
-            Value::Boolean(false) => Some(0.0),
-            Value::Integer(int) => int.as_f64(),
-            Value::F32(float) => Some(f64::from(*float)),
-            Value::F64(float) => Some(*float),
-            Value::String(str) => floatify(str.as_str()?),
+            Value::Boolean(true) => Some(1.0.into()),
+            Value::Boolean(false) => Some(0.0.into()),
+            Value::Integer(int) => int.as_f64().map(Number::from),
+            Value::F32(float) => Some(f64::from(*float).into()),
+            Value::F64(float) => Some((*float).into()),
+            Value::String(str) => Number::parse(str.as_str()?, options.floatify),
             Value::Array(array) => {
+                // Ids only when every element has one so they can not collide with indexes
+                let ids = options.array_id.and_then(|id| {
+                    let keys = array
+                        .iter()
+                        .map(|value| array_id(value, id))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some((id, keys))
+                });
                 for (index, value) in array.iter().enumerate() {
                     let mut current_key = current_key.clone();
-                    current_key.push(Key::Int(index));
-                    _ = inner(result, current_key, value);
+                    if let Some((id, keys)) = &ids {
+                        current_key.push(keys[index]);
+                        _ = inner(result, options, current_key, value, Some(id));
+                    } else {
+                        current_key.push(Key::Int(index));
+                        _ = inner(result, options, current_key, value, None);
+                    }
                 }
                 None
             }
             Value::Map(map) => {
                 for (key, value) in map {
+                    if skip_member.is_some() && key.as_str() == skip_member {
+                        continue;
+                    }
                     let mut current_key = current_key.clone();
                     current_key.push(key.try_into().ok()?);
-                    _ = inner(result, current_key, value);
+                    _ = inner(result, options, current_key, value, None);
                 }
                 None
             }
@@ -153,7 +376,81 @@ pub fn messagepack(value: &rmpv::Value) -> Vec<(Vec<Key<'_>>, f64)> {
         Some(())
     }
     let mut result = Vec::new();
-    _ = inner(&mut result, Vec::new(), value);
+    _ = inner(&mut result, options, Vec::new(), value, None);
+    result
+}
+
+pub fn cbor<'cbor>(
+    value: &'cbor ciborium::Value,
+    options: Options<'_>,
+) -> Vec<(Vec<Key<'cbor>>, Number<'cbor>)> {
+    use ciborium::Value;
+    fn array_id<'cbor>(value: &'cbor Value, id: &str) -> Option<Key<'cbor>> {
+        let (_, value) = value
+            .as_map()?
+            .iter()
+            .find(|(key, _)| key.as_text() == Some(id))?;
+        match value {
+            Value::Text(_) | Value::Integer(_) => value.try_into().ok(),
+            _ => None,
+        }
+    }
+    fn inner<'cbor>(
+        result: &mut Vec<(Vec<Key<'cbor>>, Number<'cbor>)>,
+        options: Options<'_>,
+        current_key: Vec<Key<'cbor>>,
+        value: &'cbor Value,
+        skip_member: Option<&str>,
+    ) -> Option<()> {
+        let simple = match value {
+            Value::Bool(true) => Some(1.0.into()),
+            Value::Bool(false) => Some(0.0.into()),
+            #[expect(clippy::cast_precision_loss)]
+            Value::Integer(int) => Some((i128::from(*int) as f64).into()),
+            Value::Float(float) => Some(*float)
+                .filter(|float| float.is_finite())
+                .map(Number::from),
+            Value::Text(text) => Number::parse(text, options.floatify),
+            Value::Tag(_, value) => return inner(result, options, current_key, value, skip_member),
+            Value::Array(array) => {
+                // Ids only when every element has one so they can not collide with indexes
+                let ids = options.array_id.and_then(|id| {
+                    let keys = array
+                        .iter()
+                        .map(|value| array_id(value, id))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some((id, keys))
+                });
+                for (index, value) in array.iter().enumerate() {
+                    let mut current_key = current_key.clone();
+                    if let Some((id, keys)) = &ids {
+                        current_key.push(keys[index]);
+                        _ = inner(result, options, current_key, value, Some(id));
+                    } else {
+                        current_key.push(Key::Int(index));
+                        _ = inner(result, options, current_key, value, None);
+                    }
+                }
+                None
+            }
+            Value::Map(map) => {
+                for (key, value) in map {
+                    if skip_member.is_some() && key.as_text() == skip_member {
+                        continue;
+                    }
+                    let mut current_key = current_key.clone();
+                    current_key.push(key.try_into().ok()?);
+                    _ = inner(result, options, current_key, value, None);
+                }
+                None
+            }
+            _ => None,
+        }?;
+        result.push((current_key, simple));
+        Some(())
+    }
+    let mut result = Vec::new();
+    _ = inner(&mut result, options, Vec::new(), value, None);
     result
 }
 
@@ -165,17 +462,22 @@ mod tests {
 
     #[track_caller]
     fn single(payload: &Payload) -> f64 {
-        match &dbg!(Values::from(payload)).unwrap() {
-            Values::Single(value) => *value,
+        match &dbg!(Values::from(payload, Options::default())).unwrap() {
+            Values::Single(number) => number.value,
             Values::Many(_) => panic!("not single"),
         }
     }
 
     #[track_caller]
     fn many<const N: usize>(payload: &Payload) -> [(Vec<Key<'_>>, f64); N] {
-        match dbg!(Values::from(payload)).unwrap() {
+        match dbg!(Values::from(payload, Options::default())).unwrap() {
             Values::Single(_) => panic!("not many"),
-            Values::Many(many) => match many.try_into() {
+            Values::Many(many) => match many
+                .into_iter()
+                .map(|(keys, number)| (keys, number.value))
+                .collect::<Vec<_>>()
+                .try_into()
+            {
                 Ok(many) => many,
                 Err(original) => panic!(
                     "different amount of entries. Expected: {N} Actual: {}",
@@ -187,9 +489,15 @@ mod tests {
 
     #[test]
     fn payload_parses_string() {
-        match dbg!(Payload::new(b"whatever".to_vec())).unwrap() {
+        match dbg!(Payload::new(
+            b"whatever".to_vec(),
+            &Format::Auto,
+            Numbers::default()
+        ))
+        .unwrap()
+        {
             Payload::String(string) => assert_eq!(string, "whatever"),
-            Payload::Json(_) | Payload::MessagePack(_) => unreachable!(),
+            Payload::Json(_) | Payload::MessagePack(_) | Payload::Cbor(_) => unreachable!(),
         }
     }
 
@@ -234,6 +542,37 @@ mod tests {
             assert_eq!(keys, &[Key::String("success")]);
             assert_float_eq!(value, 1.0, abs <= 0.001);
         }
+
+        #[test]
+        fn array_id() {
+            let payload = json!({
+                "sensors": [{"id": "28-abc", "t": 20.1}, {"id": 5, "t": 13.37}],
+                "mixed": [{"id": 1, "t": 42}, {"t": 43}],
+            });
+            let options = Options {
+                array_id: Some("id"),
+                ..Options::default()
+            };
+            let result = json(&payload, options);
+            let keys = result
+                .iter()
+                .map(|(keys, _)| keys.as_slice())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                keys,
+                [
+                    [Key::String("mixed"), Key::Int(0), Key::String("id")].as_slice(),
+                    &[Key::String("mixed"), Key::Int(0), Key::String("t")],
+                    &[Key::String("mixed"), Key::Int(1), Key::String("t")],
+                    &[
+                        Key::String("sensors"),
+                        Key::String("28-abc"),
+                        Key::String("t")
+                    ],
+                    &[Key::String("sensors"), Key::Int(5), Key::String("t")],
+                ]
+            );
+        }
     }
 
     mod messagepack {
@@ -246,7 +585,7 @@ mod tests {
             let value = Value::F64(12.3);
             let mut buffer = Vec::new();
             rmpv::encode::write_value(&mut buffer, &value).unwrap();
-            match Payload::new(buffer).unwrap() {
+            match Payload::new(buffer, &Format::Auto, Numbers::default()).unwrap() {
                 Payload::MessagePack(Value::F64(value)) => {
                     assert_float_eq!(value, 12.3, abs <= 0.001);
                 }
@@ -254,6 +593,22 @@ mod tests {
             }
         }
 
+        #[test]
+        fn as_json_works() {
+            let payload = Payload::MessagePack(Value::Map(vec![
+                (Value::String("code".into()), Value::Integer(200.into())),
+                (Value::String("temp".into()), Value::F64(21.5)),
+                (
+                    Value::String("list".into()),
+                    Value::Array(vec![Value::Boolean(true), Value::Nil]),
+                ),
+            ]));
+            assert_eq!(
+                payload.as_json().unwrap().into_owned(),
+                serde_json::json!({"code": 200, "temp": 21.5, "list": [true, null]})
+            );
+        }
+
         #[test]
         fn plain_f64() {
             let value = single(&Payload::MessagePack(Value::F64(12.3)));
@@ -289,5 +644,122 @@ mod tests {
             assert_eq!(keys, &[Key::String("success")]);
             assert_float_eq!(value, 1.0, abs <= 0.001);
         }
+
+        #[test]
+        fn array_id() {
+            let sensor = |id: Value, temperature: f64| {
+                Value::Map(vec![
+                    (Value::String("id".into()), id),
+                    (Value::String("t".into()), Value::F64(temperature)),
+                ])
+            };
+            let payload = Value::Map(vec![(
+                Value::String("sensors".into()),
+                Value::Array(vec![
+                    sensor(Value::String("28-abc".into()), 20.1),
+                    sensor(Value::Integer(5.into()), 13.37),
+                ]),
+            )]);
+            let options = Options {
+                array_id: Some("id"),
+                ..Options::default()
+            };
+            let result = messagepack(&payload, options);
+            let keys = result
+                .iter()
+                .map(|(keys, _)| keys.as_slice())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                keys,
+                [
+                    [
+                        Key::String("sensors"),
+                        Key::String("28-abc"),
+                        Key::String("t")
+                    ]
+                    .as_slice(),
+                    &[Key::String("sensors"), Key::Int(5), Key::String("t")],
+                ]
+            );
+        }
+    }
+
+    mod cbor {
+        use ciborium::Value;
+
+        use super::*;
+
+        #[test]
+        fn parse_forced() {
+            let value = Value::Float(12.3);
+            let mut buffer = Vec::new();
+            ciborium::into_writer(&value, &mut buffer).unwrap();
+            match Payload::new(buffer, &Format::Cbor, Numbers::default()).unwrap() {
+                Payload::Cbor(Value::Float(value)) => {
+                    assert_float_eq!(value, 12.3, abs <= 0.001);
+                }
+                _ => panic!("unexpected value"),
+            }
+        }
+
+        #[test]
+        fn parse_forced_utf8() {
+            // Small CBOR integers are valid UTF-8 and would be parsed as JSON with Format::Auto
+            match Payload::new(b"\x17".to_vec(), &Format::Cbor, Numbers::default()).unwrap() {
+                Payload::Cbor(Value::Integer(int)) => assert_eq!(i128::from(int), 23),
+                _ => panic!("unexpected value"),
+            }
+        }
+
+        #[test]
+        fn plain_float() {
+            let value = single(&Payload::Cbor(Value::Float(12.3)));
+            assert_float_eq!(value, 12.3, abs <= 0.001);
+        }
+
+        #[test]
+        fn similar_to_json_example() {
+            let payload = Payload::Cbor(Value::Map(vec![
+                (Value::Text("code".into()), Value::Integer(200.into())),
+                (Value::Text("success".into()), Value::Bool(true)),
+                (
+                    Value::Text("payload".into()),
+                    Value::Map(vec![
+                        (
+                            Value::Text("features".into()),
+                            Value::Array(vec![
+                                Value::Text("serde".into()),
+                                Value::Text("ciborium".into()),
+                            ]),
+                        ),
+                        (Value::Text("homepage".into()), Value::Null),
+                    ]),
+                ),
+            ]));
+            let [code, success] = many(&payload);
+
+            let (keys, value) = code;
+            assert_eq!(keys, &[Key::String("code")]);
+            assert_float_eq!(value, 200.0, abs <= 0.001);
+
+            let (keys, value) = success;
+            assert_eq!(keys, &[Key::String("success")]);
+            assert_float_eq!(value, 1.0, abs <= 0.001);
+        }
+
+        #[test]
+        fn as_json_works() {
+            let payload = Payload::Cbor(Value::Map(vec![
+                (Value::Text("code".into()), Value::Integer(200.into())),
+                (
+                    Value::Text("tagged".into()),
+                    Value::Tag(1, Box::new(Value::Float(21.5))),
+                ),
+            ]));
+            assert_eq!(
+                payload.as_json().unwrap().into_owned(),
+                serde_json::json!({"code": 200, "tagged": 21.5})
+            );
+        }
     }
 }
@@ -0,0 +1,139 @@
+use crate::aggregate::Windows;
+use crate::deadband::LastWritten;
+use crate::dedup::Seen;
+use crate::message::Message;
+use crate::payload::Format;
+use crate::point::Point;
+use crate::rate::Counters;
+use crate::rules::Rules;
+use crate::sparkplug::Sparkplug;
+use crate::timestamp_guard::Outcomes;
+
+/// Converts messages into points and keeps the state between messages
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    rules: Rules,
+    /// Explain skipped parts of messages
+    verbose: bool,
+    seen: Seen,
+    sparkplug: Sparkplug,
+    timestamp_outcomes: Outcomes,
+    counters: Counters,
+    windows: Windows,
+    last_written: LastWritten,
+}
+
+impl Pipeline {
+    pub fn new(rules: Rules, verbose: bool) -> Self {
+        Self {
+            rules,
+            verbose,
+            ..Self::default()
+        }
+    }
+
+    pub const fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Amount of points with timestamps outside of the accepted ones since the start
+    pub const fn timestamp_outcomes(&self) -> Outcomes {
+        self.timestamp_outcomes
+    }
+
+    pub fn process(&mut self, message: Message) -> Vec<Point> {
+        let nanos = message.nanos();
+        let topic = message.topic().to_owned();
+        let rule = self.rules.get(&topic);
+        if let Some(dedup) = &rule.dedup
+            && self
+                .seen
+                .is_duplicate(dedup, &message, &rule.format, rule.numbers)
+        {
+            return self.flush(nanos);
+        }
+        let points = if let Some(script) = &rule.script {
+            script.convert(&message)
+        } else {
+            match rule.format {
+                Format::Sparkplug => self.sparkplug.process(&message, rule.floatify_options()),
+                _ => message.into_points(rule, self.verbose),
+            }
+        };
+        let points = match &rule.timestamp_guard {
+            Some(guard) => self.timestamp_outcomes.guard(guard, nanos, points),
+            None => points,
+        };
+        let points = match &rule.rate {
+            Some(rate) => self.counters.derive(rate, points),
+            None => points,
+        };
+        let mut points = if let Some(aggregate) = &rule.aggregate {
+            self.windows.add(aggregate, &topic, points);
+            Vec::new()
+        } else {
+            self.write(&topic, points)
+        };
+        points.extend(self.flush(nanos));
+        points
+    }
+
+    /// Points of the aggregation windows which ended until now (nanoseconds since UNIX epoch)
+    pub fn flush(&mut self, now: u128) -> Vec<Point> {
+        self.windows
+            .flush(now)
+            .into_iter()
+            .flat_map(|(topic, point)| self.write(&topic, vec![point]))
+            .collect()
+    }
+
+    /// Stages between the conversion of a message and the output
+    fn write(&mut self, topic: &str, points: Vec<Point>) -> Vec<Point> {
+        match &self.rules.get(topic).deadband {
+            Some(deadband) => self.last_written.filter(deadband, points),
+            None => points,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{SECOND, render};
+
+    #[test]
+    fn rate_of_payload_key() {
+        let rules = toml::from_str::<Rules>(
+            r#"
+            [[rule]]
+            topic = "meter"
+            topic_tags = false
+            rate = { keys = ["total"], per = "hour" }
+            "#,
+        )
+        .unwrap();
+        let mut pipeline = Pipeline::new(rules, false);
+        let points = [
+            (0, r#"{"total": 10, "power": 5}"#),
+            (1800, r#"{"total": 10.5, "power": 6}"#),
+        ]
+        .into_iter()
+        .flat_map(|(seconds, payload)| {
+            pipeline.process(Message::new(
+                seconds * SECOND,
+                "meter".to_owned(),
+                payload.into(),
+            ))
+        })
+        .collect::<Vec<_>>();
+        assert_eq!(
+            render(&points),
+            [
+                "measurement,key1=power,keySegments=1 value=5 0",
+                "measurement,key1=total,keySegments=1 value=10 0",
+                "measurement,key1=power,keySegments=1 value=6 1800000000000",
+                "measurement,key1=total,keySegments=1 value=10.5,value_rate=1 1800000000000",
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::SystemTime;
+
+/// Single data point as it will end up in the database.
+///
+/// Independent of any output format. See [`crate::line_protocol`] for rendering it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    /// Tags in the order they should be rendered
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    /// Nanoseconds since UNIX epoch
+    pub timestamp: u128,
+}
+
+/// Values converted from payloads are floats, other types are only kept from line protocol payloads
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    UInteger(u64),
+    String(String),
+    Boolean(bool),
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl FieldValue {
+    /// Numbers as float, `None` for strings and booleans
+    #[expect(clippy::cast_precision_loss)]
+    pub const fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(float) => Some(*float),
+            Self::Integer(int) => Some(*int as f64),
+            Self::UInteger(uint) => Some(*uint as f64),
+            Self::String(_) | Self::Boolean(_) => None,
+        }
+    }
+}
+
+/// Measurement, tags and field key which identify the values of a field over time
+pub type SeriesKey = (String, Vec<(String, String)>, String);
+
+/// Current time in nanoseconds since UNIX epoch
+pub fn nanos_now() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+/// Seconds in nanoseconds like the timestamps of messages and points
+pub const fn seconds_to_nanos(seconds: u64) -> u128 {
+    seconds as u128 * 1_000_000_000
+}
+
+impl Point {
+    pub fn new(measurement: impl Into<String>, timestamp: u128) -> Self {
+        Self {
+            measurement: measurement.into(),
+            tags: Vec::new(),
+            fields: Vec::new(),
+            timestamp,
+        }
+    }
+
+    pub fn series_key(&self, field: &str) -> SeriesKey {
+        (
+            self.measurement.clone(),
+            self.tags.clone(),
+            field.to_owned(),
+        )
+    }
+
+    pub fn add_field(&mut self, key: impl Into<String>, value: f64) {
+        self.fields.push((key.into(), FieldValue::Float(value)));
+    }
+}
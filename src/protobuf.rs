@@ -0,0 +1,208 @@
+//! Decode Protocol Buffers payloads with descriptors loaded at runtime
+
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use protobuf::descriptor::FileDescriptorSet;
+use protobuf::descriptor::field_descriptor_proto::Type;
+use protobuf::reflect::{FileDescriptor, MessageDescriptor, ReflectFieldRef, ReflectValueRef};
+use protobuf::{Message as _, MessageDyn};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Protobuf message type of the payload
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Config")]
+pub struct Protobuf {
+    message: MessageDescriptor,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// `.proto` file or a compiled descriptor set like `protoc --descriptor_set_out` creates
+    file: PathBuf,
+
+    /// Fully qualified message type like `package.Message`
+    message: String,
+
+    /// Directories to search for imports of `.proto` files. Defaults to the directory of the file.
+    #[serde(default)]
+    includes: Vec<PathBuf>,
+}
+
+impl TryFrom<Config> for Protobuf {
+    type Error = String;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        load(&config)
+            .map(|message| Self { message })
+            .map_err(|err| format!("{}: {err:#}", config.file.display()))
+    }
+}
+
+fn load(config: &Config) -> anyhow::Result<MessageDescriptor> {
+    let protos = if config
+        .file
+        .extension()
+        .is_some_and(|extension| extension == "proto")
+    {
+        let mut parser = protobuf_parse::Parser::new();
+        parser.pure().input(&config.file);
+        if config.includes.is_empty() {
+            parser.include(config.file.parent().unwrap_or(&PathBuf::from(".")));
+        } else {
+            parser.includes(&config.includes);
+        }
+        parser.parse_and_typecheck()?.file_descriptors
+    } else {
+        let bytes = std::fs::read(&config.file).context("failed to read descriptor set")?;
+        FileDescriptorSet::parse_from_bytes(&bytes)
+            .context("failed to parse descriptor set")?
+            .file
+    };
+    let files = FileDescriptor::new_dynamic_fds(protos, &[])?;
+    let name = config.message.trim_start_matches('.');
+    files
+        .iter()
+        .find_map(|file| file.message_by_full_name(&format!(".{name}")))
+        .with_context(|| format!("message type {name} not found"))
+}
+
+impl Protobuf {
+    /// Decode into JSON so it can be flattened the same way as JSON payloads
+    pub fn decode(&self, payload: &[u8]) -> Option<Value> {
+        let message = self.message.parse_from_bytes(payload).ok()?;
+        Some(message_to_json(&*message))
+    }
+}
+
+fn message_to_json(message: &dyn MessageDyn) -> Value {
+    let mut object = serde_json::Map::new();
+    for field in message.descriptor_dyn().fields() {
+        let value = match field.get_reflect(message) {
+            // proto3 does not transmit default values so they are not present
+            ReflectFieldRef::Optional(_) if field.proto().type_() != Type::TYPE_MESSAGE => {
+                value_to_json(field.get_singular_field_or_default(message))
+            }
+            ReflectFieldRef::Optional(optional) => match optional.value() {
+                Some(value) => value_to_json(value),
+                None => continue,
+            },
+            ReflectFieldRef::Repeated(repeated) => {
+                repeated.into_iter().map(value_to_json).collect()
+            }
+            ReflectFieldRef::Map(map) => (&map)
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value_to_json(value)))
+                .collect(),
+        };
+        object.insert(field.name().to_owned(), value);
+    }
+    Value::Object(object)
+}
+
+fn value_to_json(value: ReflectValueRef<'_>) -> Value {
+    match value {
+        ReflectValueRef::U32(int) => int.into(),
+        ReflectValueRef::U64(int) => int.into(),
+        ReflectValueRef::I32(int) | ReflectValueRef::Enum(_, int) => int.into(),
+        ReflectValueRef::I64(int) => int.into(),
+        ReflectValueRef::F32(float) => serde_json::Number::from_f64(f64::from(float)).into(),
+        ReflectValueRef::F64(float) => serde_json::Number::from_f64(float).into(),
+        ReflectValueRef::Bool(bool) => bool.into(),
+        ReflectValueRef::String(string) => string.into(),
+        ReflectValueRef::Bytes(_) => Value::Null,
+        ReflectValueRef::Message(message) => message_to_json(&*message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::reflect::ReflectValueBox;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    const PROTO: &str = r#"
+        syntax = "proto3";
+        package factory;
+
+        message Telemetry {
+            string machine = 1;
+            double temperature = 2;
+            uint32 errors = 3;
+            repeated float currents = 4;
+            Status status = 5;
+            map<string, int64> counters = 6;
+        }
+
+        message Status {
+            bool running = 1;
+        }
+    "#;
+
+    #[track_caller]
+    fn load_example(test: &str, message: &str) -> Result<Protobuf, String> {
+        let dir = TempDir::new(&format!("protobuf-{test}"));
+        Protobuf::try_from(Config {
+            file: dir.write("telemetry.proto", PROTO),
+            message: message.to_owned(),
+            includes: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn decode_works() {
+        let protobuf = load_example("decode", "factory.Telemetry").unwrap();
+        let descriptor = &protobuf.message;
+        let mut message = descriptor.new_instance();
+        let field = |name: &str| descriptor.field_by_name(name).unwrap();
+        field("machine").set_singular_field(&mut *message, ReflectValueBox::String("m1".into()));
+        field("temperature").set_singular_field(&mut *message, ReflectValueBox::F64(21.5));
+        let currents = field("currents");
+        let mut repeated = currents.mut_repeated(&mut *message);
+        repeated.push(ReflectValueBox::F32(1.5));
+        repeated.push(ReflectValueBox::F32(2.5));
+        field("counters").mut_map(&mut *message).insert(
+            ReflectValueBox::String("parts".into()),
+            ReflectValueBox::I64(42),
+        );
+        let payload = message.write_to_bytes_dyn().unwrap();
+
+        assert_eq!(
+            protobuf.decode(&payload).unwrap(),
+            serde_json::json!({
+                "machine": "m1",
+                "temperature": 21.5,
+                "errors": 0,
+                "currents": [1.5, 2.5],
+                "counters": {"parts": 42},
+            })
+        );
+    }
+
+    #[test]
+    fn decode_invalid_fails() {
+        let protobuf = load_example("invalid", "factory.Telemetry").unwrap();
+        assert_eq!(protobuf.decode(&[0xff, 0xff, 0xff]), None);
+    }
+
+    #[test]
+    fn unknown_message_fails() {
+        let err = load_example("unknown", "factory.Unknown").unwrap_err();
+        assert!(err.contains("message type factory.Unknown not found"));
+    }
+
+    #[test]
+    fn missing_file_fails() {
+        let err = toml::from_str::<Protobuf>(
+            r#"
+            file = "does-not-exist.proto"
+            message = "factory.Telemetry"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("does-not-exist.proto"));
+    }
+}
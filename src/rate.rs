@@ -0,0 +1,177 @@
+//! Derive rates from counters like energy totals so they do not need a derivative on every query
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::message::key_path;
+use crate::point::{FieldValue, Point, SeriesKey};
+use crate::rules::wildcard_matches;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Per {
+    #[default]
+    Second,
+    Minute,
+    Hour,
+}
+
+impl Per {
+    const fn seconds(self) -> f64 {
+        match self {
+            Self::Second => 1.0,
+            Self::Minute => 60.0,
+            Self::Hour => 3600.0,
+        }
+    }
+}
+
+/// Which counters get a rate and how it is written
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rate {
+    /// Counters with `*` wildcards. All numbers when empty.
+    ///
+    /// Payload values are selected by their key path like `meter.total`, the fields of `extract` and `script` by their key.
+    #[serde(default)]
+    pub keys: Vec<String>,
+
+    /// Time unit of the rate
+    #[serde(default)]
+    pub per: Per,
+
+    /// Write the rate instead of the counter
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// Last value and its timestamp per counter
+#[derive(Debug, Default)]
+pub struct Counters(HashMap<SeriesKey, (f64, u128)>);
+
+impl Counters {
+    /// Add a `{key}_rate` field of the selected counters.
+    ///
+    /// The first value of a counter, a decreasing value (counter reset) or an unchanged timestamp only remembers the value without rate.
+    /// Points without fields left are removed.
+    pub fn derive(&mut self, rate: &Rate, points: Vec<Point>) -> Vec<Point> {
+        points
+            .into_iter()
+            .filter_map(|mut point| {
+                let key_path = key_path(&point);
+                let mut fields = Vec::with_capacity(point.fields.len());
+                for (key, value) in std::mem::take(&mut point.fields) {
+                    let name = key_path.as_deref().unwrap_or(&key);
+                    let counter = value.as_f64().filter(|_| {
+                        rate.keys.is_empty()
+                            || rate
+                                .keys
+                                .iter()
+                                .any(|pattern| wildcard_matches(pattern, name))
+                    });
+                    let Some(counter) = counter else {
+                        fields.push((key, value));
+                        continue;
+                    };
+                    let series = point.series_key(&key);
+                    let last = self.0.insert(series, (counter, point.timestamp));
+                    let derived = last
+                        .filter(|(last, timestamp)| {
+                            counter >= *last && point.timestamp > *timestamp
+                        })
+                        .map(|(last, timestamp)| {
+                            #[expect(clippy::cast_precision_loss)]
+                            let seconds = (point.timestamp - timestamp) as f64 / 1e9;
+                            (counter - last) / seconds * rate.per.seconds()
+                        });
+                    let rate_key = format!("{key}_rate");
+                    if !rate.replace {
+                        fields.push((key, value));
+                    }
+                    if let Some(derived) = derived {
+                        fields.push((rate_key, FieldValue::Float(derived)));
+                    }
+                }
+                point.fields = fields;
+                (!point.fields.is_empty()).then_some(point)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{point, render};
+
+    fn derive(rate: &Rate, values: &[(u128, f64)]) -> Vec<String> {
+        let mut counters = Counters::default();
+        let points = values
+            .iter()
+            .flat_map(|(seconds, energy)| {
+                let mut point = point(*seconds, "energy", *energy);
+                point.add_field("voltage", 230.0);
+                counters.derive(rate, vec![point])
+            })
+            .collect::<Vec<_>>();
+        render(&points)
+    }
+
+    #[test]
+    fn rate_per_hour() {
+        let rate = Rate {
+            keys: vec!["energy".to_owned()],
+            per: Per::Hour,
+            replace: false,
+        };
+        assert_eq!(
+            derive(&rate, &[(0, 10.0), (1800, 10.5)]),
+            [
+                "measurement,topic=meter energy=10,voltage=230 0",
+                "measurement,topic=meter energy=10.5,energy_rate=1,voltage=230 1800000000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn counter_reset_has_no_rate() {
+        let rate = Rate {
+            keys: vec!["energy".to_owned()],
+            replace: true,
+            ..Rate::default()
+        };
+        assert_eq!(
+            derive(&rate, &[(0, 10.0), (10, 20.0), (20, 5.0), (30, 7.0)]),
+            [
+                "measurement,topic=meter voltage=230 0",
+                "measurement,topic=meter energy_rate=1,voltage=230 10000000000",
+                "measurement,topic=meter voltage=230 20000000000",
+                "measurement,topic=meter energy_rate=0.2,voltage=230 30000000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn all_fields_by_default() {
+        let rate = Rate {
+            replace: true,
+            ..Rate::default()
+        };
+        assert_eq!(
+            derive(&rate, &[(0, 10.0), (0, 11.0), (2, 12.0)]),
+            ["measurement,topic=meter energy_rate=0.5,voltage_rate=0 2000000000"]
+        );
+    }
+
+    #[test]
+    fn non_numeric_fields_are_kept() {
+        let mut counters = Counters::default();
+        let point = point(0, "state", FieldValue::String("on".to_owned()));
+        let rate = Rate {
+            replace: true,
+            ..Rate::default()
+        };
+        assert_eq!(counters.derive(&rate, vec![point.clone()]), [point]);
+    }
+}
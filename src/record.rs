@@ -0,0 +1,134 @@
+//! Record raw MQTT messages to a file which can be replayed later
+
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+pub struct Recorder {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl Recorder {
+    /// Append to the file at `path`.
+    /// When it grows beyond `max_bytes` it is rotated to `path.1`, `path.2`, … keeping `keep` old files.
+    pub fn new(path: PathBuf, max_bytes: u64, keep: usize) -> anyhow::Result<Self> {
+        let file = open(&path)?;
+        let size = file
+            .metadata()
+            .context("failed to read record file size")?
+            .len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_bytes,
+            keep,
+        })
+    }
+
+    pub fn record(
+        &mut self,
+        nanos: u128,
+        topic: &str,
+        qos: u8,
+        dup: bool,
+        retain: bool,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        if self.size >= self.max_bytes {
+            self.rotate()?;
+        }
+        let line = record_line(nanos, topic, qos, dup, retain, payload);
+        writeln!(self.file, "{line}").context("failed to write record file")?;
+        self.size = self.size.saturating_add(line.len() as u64 + 1);
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        if self.keep == 0 {
+            std::fs::remove_file(&self.path).context("failed to remove full record file")?;
+        } else {
+            for index in (1..self.keep).rev() {
+                let from = rotated(&self.path, index);
+                if from.exists() {
+                    std::fs::rename(&from, rotated(&self.path, index + 1))
+                        .context("failed to rotate record file")?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))
+                .context("failed to rotate record file")?;
+        }
+        self.file = open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("failed to open record file")
+}
+
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{index}"));
+    path.into()
+}
+
+/// Same format the `replay` subcommand reads
+fn record_line(
+    nanos: u128,
+    topic: &str,
+    qos: u8,
+    dup: bool,
+    retain: bool,
+    payload: &[u8],
+) -> String {
+    serde_json::json!({
+        "timestamp": u64::try_from(nanos).unwrap_or(u64::MAX),
+        "topic": topic,
+        "qos": qos,
+        "dup": dup,
+        "retain": retain,
+        "payload": BASE64.encode(payload),
+    })
+    .to_string()
+}
+
+#[test]
+fn record_line_works() {
+    assert_eq!(
+        record_line(1337, "foo/bar", 1, false, false, b"42"),
+        r#"{"dup":false,"payload":"NDI=","qos":1,"retain":false,"timestamp":1337,"topic":"foo/bar"}"#
+    );
+}
+
+#[test]
+fn rotation_works() {
+    let dir = crate::testing::TempDir::new("record");
+    let path = dir.path("record.jsonl");
+
+    let mut recorder = Recorder::new(path.clone(), 1, 2).unwrap();
+    for nanos in 1..=4 {
+        recorder
+            .record(nanos, "foo", 0, false, false, b"42")
+            .unwrap();
+    }
+
+    let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+    assert!(read(&path).contains(r#""timestamp":4"#));
+    assert!(read(&rotated(&path, 1)).contains(r#""timestamp":3"#));
+    assert!(read(&rotated(&path, 2)).contains(r#""timestamp":2"#));
+    assert!(!rotated(&path, 3).exists());
+}
@@ -0,0 +1,162 @@
+//! Read recorded MQTT messages from a file
+
+use std::fs::File;
+use std::io::{BufRead as _, BufReader};
+use std::path::Path;
+
+use anyhow::Context as _;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use crate::message::Message;
+use crate::point;
+
+/// Read the recording line by line, empty lines, duplicate and retained messages are skipped
+pub fn read(path: &Path) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Message>>> {
+    let file = File::open(path).context("failed to open replay file")?;
+    let lines = BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let line_number = index.saturating_add(1);
+            match line {
+                Ok(line) if line.trim().is_empty() => None,
+                Ok(line) => parse_line(&line, point::nanos_now())
+                    .with_context(|| format!("line {line_number}"))
+                    .transpose(),
+                Err(err) => Some(Err(err).with_context(|| format!("line {line_number}"))),
+            }
+        });
+    Ok(lines)
+}
+
+/// Parse a single line of a recording.
+///
+/// Either a JSON object like `{"timestamp": 1337, "topic": "foo/bar", "payload": "NDI="}` with the timestamp in nanoseconds since UNIX epoch and a base64 encoded payload.
+/// Or a `mosquitto_sub -v` line (`topic payload`), optionally prefixed by the timestamp `mosquitto_sub -F '%U %t %p'` creates.
+/// Lines without timestamp get `fallback_nanos`.
+///
+/// Duplicate and retained messages are skipped just like the MQTT connection does.
+/// Duplicates were already received and the time of retained messages is unknown.
+fn parse_line(line: &str, fallback_nanos: u128) -> anyhow::Result<Option<Message>> {
+    if line.starts_with('{') {
+        return parse_json(line);
+    }
+
+    let (nanos, rest) = line
+        .split_once(' ')
+        .and_then(|(first, rest)| Some((unix_timestamp(first)?, rest)))
+        .unwrap_or((fallback_nanos, line));
+    let (topic, payload) = rest.split_once(' ').unwrap_or((rest, ""));
+    anyhow::ensure!(!topic.is_empty(), "topic is missing");
+    Ok(Some(Message::new(
+        nanos,
+        topic.to_owned(),
+        payload.as_bytes().to_vec(),
+    )))
+}
+
+fn parse_json(line: &str) -> anyhow::Result<Option<Message>> {
+    let json = serde_json::from_str::<serde_json::Value>(line).context("invalid JSON")?;
+    let flag = |key: &str| json.get(key).and_then(serde_json::Value::as_bool) == Some(true);
+    if flag("dup") || flag("retain") {
+        return Ok(None);
+    }
+    let nanos = json
+        .get("timestamp")
+        .and_then(serde_json::Value::as_u64)
+        .context("timestamp (nanoseconds since UNIX epoch) is missing")?;
+    let topic = json
+        .get("topic")
+        .and_then(serde_json::Value::as_str)
+        .context("topic is missing")?;
+    let payload = json
+        .get("payload")
+        .and_then(serde_json::Value::as_str)
+        .context("payload is missing")?;
+    let payload = BASE64.decode(payload).context("payload is not base64")?;
+    Ok(Some(Message::new(nanos.into(), topic.to_owned(), payload)))
+}
+
+/// Parse `1695215712.123456789` (seconds with nanoseconds) into nanoseconds
+fn unix_timestamp(input: &str) -> Option<u128> {
+    let (seconds, fraction) = input.split_once('.')?;
+    if seconds.is_empty()
+        || fraction.is_empty()
+        || fraction.len() > 9
+        || !seconds
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    let seconds = seconds.parse::<u128>().ok()?;
+    let nanos = format!("{fraction:0<9}").parse::<u128>().ok()?;
+    seconds.checked_mul(1_000_000_000)?.checked_add(nanos)
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::json(
+    r#"{"timestamp": 1337, "topic": "foo/bar", "payload": "NDI="}"#,
+    1337,
+    "foo/bar",
+    b"42"
+)]
+#[case::json_record(
+    r#"{"dup":false,"payload":"NDI=","qos":1,"retain":false,"timestamp":1337,"topic":"foo/bar"}"#,
+    1337,
+    "foo/bar",
+    b"42"
+)]
+#[case::mosquitto("foo/bar 42", 666, "foo/bar", b"42")]
+#[case::mosquitto_spaces("foo/bar 12.3 °C", 666, "foo/bar", "12.3 °C".as_bytes())]
+#[case::mosquitto_empty("foo/bar", 666, "foo/bar", b"")]
+#[case::mosquitto_timestamp(
+    "1695215712.123456789 foo/bar 42",
+    1_695_215_712_123_456_789,
+    "foo/bar",
+    b"42"
+)]
+#[case::mosquitto_timestamp_short(
+    "1695215712.5 foo/bar 42",
+    1_695_215_712_500_000_000,
+    "foo/bar",
+    b"42"
+)]
+#[case::mosquitto_numeric_topic("1695215712 42", 666, "1695215712", b"42")]
+fn parse_line_works(
+    #[case] line: &str,
+    #[case] nanos: u128,
+    #[case] topic: &str,
+    #[case] payload: &[u8],
+) {
+    let message = parse_line(line, 666).unwrap().unwrap();
+    assert_eq!(
+        message,
+        Message::new(nanos, topic.to_owned(), payload.to_vec())
+    );
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::retained(
+    r#"{"dup":false,"payload":"NDI=","qos":1,"retain":true,"timestamp":1337,"topic":"foo/bar"}"#
+)]
+#[case::duplicate(
+    r#"{"dup":true,"payload":"NDI=","qos":1,"retain":false,"timestamp":1337,"topic":"foo/bar"}"#
+)]
+fn parse_line_skips(#[case] line: &str) {
+    assert_eq!(parse_line(line, 666).unwrap(), None);
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case::json_without_timestamp(r#"{"topic": "foo/bar", "payload": "NDI="}"#)]
+#[case::json_invalid_base64(r#"{"timestamp": 1337, "topic": "foo/bar", "payload": "42"}"#)]
+#[case::json_invalid("{whatever")]
+#[case::mosquitto_without_topic("1695215712.123456789 ")]
+fn parse_line_fails(#[case] line: &str) {
+    assert!(parse_line(line, 666).is_err());
+}
@@ -0,0 +1,411 @@
+//! Per topic configuration of how payloads are converted.
+//!
+//! Loaded from a TOML file with `[[rule]]` entries. The first rule with a matching topic filter is used.
+//!
+//! ```toml
+//! states = { open = 1, closed = 0 }
+//!
+//! [[rule]]
+//! topic = "zigbee2mqtt/+"
+//! keys.exclude = ["linkquality", "update.*"]
+//! keys.rename = { temperature_c = "temperature", state = "mode" }
+//! keys.tags = ["state"]
+//!
+//! [[rule]]
+//! topic = "factory/+/telemetry"
+//! format.protobuf = { file = "telemetry.proto", message = "factory.Telemetry" }
+//!
+//! [[rule]]
+//! topic = "esp/+/climate"
+//! format.csv = { columns = ["temperature", "humidity"], delimiter = ";" }
+//!
+//! [[rule]]
+//! topic = "spBv1.0/#"
+//! format = "sparkplug"
+//!
+//! [[rule]]
+//! topic = "api/meter"
+//! extract.fields = { power = "$.data.meter.power" }
+//! extract.tags = { meter = "$.data.meter.id" }
+//!
+//! [[rule]]
+//! topic = "shellies/+/power"
+//! deadband = { absolute = 5, heartbeat_seconds = 300 }
+//!
+//! [[rule]]
+//! topic = "sensors/#"
+//! timestamp_guard = { past_seconds = 86400, future_seconds = 60, action = "reject", dead_letter = "rejected.lp" }
+//!
+//! [[rule]]
+//! topic = "bridge/#"
+//! dedup = { window_seconds = 10, id = "$.message_id" }
+//!
+//! [[rule]]
+//! topic = "meter/+/energy"
+//! rate = { keys = ["total"], per = "hour" }
+//!
+//! [[rule]]
+//! topic = "meter/+/power"
+//! aggregate = { window_seconds = 10, functions = ["mean", "max"] }
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+
+use crate::aggregate::Aggregate;
+use crate::deadband::Deadband;
+use crate::dedup::Dedup;
+use crate::extract::Extract;
+use crate::floatify::{self, Numbers, States};
+use crate::payload::Format;
+use crate::rate::Rate;
+use crate::script::Script;
+use crate::timestamp_guard::TimestampGuard;
+use crate::topic_filter;
+use crate::transform::Transforms;
+use crate::units::Units;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(from = "Config")]
+pub struct Rules {
+    rules: Vec<Rule>,
+
+    /// Used when no rule matches
+    fallback: Rule,
+}
+
+/// Content of the config file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// States of all rules, rules can override them
+    #[serde(default)]
+    states: States,
+
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+impl From<Config> for Rules {
+    fn from(config: Config) -> Self {
+        let mut rules = config.rules;
+        for rule in &mut rules {
+            rule.states.extend_missing(&config.states);
+        }
+        let fallback = Rule {
+            states: config.states,
+            ..Rule::default()
+        };
+        Self { rules, fallback }
+    }
+}
+
+impl Rules {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path).context("failed to read config file")?;
+        toml::from_str(&content).context("failed to parse config file")
+    }
+
+    /// First rule matching the topic
+    pub fn find(&self, topic: &str) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .find(|rule| topic_filter::matches(&rule.topic, topic))
+    }
+
+    /// First rule matching the topic or the default rule
+    pub fn get(&self, topic: &str) -> &Rule {
+        self.find(topic).unwrap_or(&self.fallback)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// MQTT topic filter this rule applies to
+    pub topic: String,
+
+    /// Decode the payload with this format instead of guessing it.
+    ///
+    /// One of `auto`, `string`, `json`, `messagepack`, `cbor`, `key-value`, `line-protocol` or `sparkplug`.
+    /// CSV needs the column names: `format.csv = { columns = ["t", "h"], delimiter = ";" }`
+    /// Protobuf needs the message type: `format.protobuf = { file = "telemetry.proto", message = "factory.Telemetry" }`
+    #[serde(default)]
+    pub format: Format,
+
+    /// Add tags of the topic like `topic`, `topic1` and `topicE1` to the points.
+    ///
+    /// Defaults to `true` except for the `line-protocol` format.
+    pub topic_tags: Option<bool>,
+
+    #[serde(default)]
+    pub keys: Keys,
+
+    /// Map strings like `open` or `heating` to numbers, case insensitive.
+    ///
+    /// Extends the global `states` and overrides built-in states like `on`.
+    #[serde(default)]
+    pub states: States,
+
+    /// Number formats of string values like `numbers = { decimal = ",", thousands = "." }` for `1.234,5`.
+    ///
+    /// `radix_prefixes = true` parses hexadecimal `0x1F` and binary `0b101` integers.
+    #[serde(default)]
+    pub numbers: Numbers,
+
+    /// Parse units of string values like `1.2 kW` and convert them per key
+    #[serde(default)]
+    pub units: Units,
+
+    /// Transform the values of matching keys like `{ raw = { scale = 0.1, offset = -40 }, "*.temperature" = "round(value / 10)" }`.
+    ///
+    /// Expressions support arithmetic, comparisons, `min`, `max`, `clamp`, `abs`, `round`, `floor` and `if(condition, then, else)`.
+    #[serde(default)]
+    pub transform: Transforms,
+
+    /// Select named fields and tags with JSONPath instead of using every value of the payload
+    #[expect(clippy::doc_markdown)]
+    pub extract: Option<Extract>,
+
+    /// Convert the payload with a Rhai script like `script = { file = "vendor.rhai", timeout_ms = 100 }` instead of the other options
+    pub script: Option<Script>,
+
+    /// Handle timestamps far off the receive time like `timestamp_guard = { past_seconds = 86400, future_seconds = 60, action = "reject" }`.
+    ///
+    /// `action` is `clamp` (default) to the receive time, `reject` to the `dead_letter` file or stderr, or `tag` with `timestamp_outside`.
+    pub timestamp_guard: Option<TimestampGuard>,
+
+    /// Drop repeated messages of the same topic and payload within a time window like `dedup = { window_seconds = 10 }`.
+    ///
+    /// `id = "$.message_id"` compares a payload value selected with JSONPath instead of the whole payload.
+    #[expect(clippy::doc_markdown)]
+    pub dedup: Option<Dedup>,
+
+    /// Add a rate field like `value_rate` of counters selected by their payload key path like `rate = { keys = ["energy"], per = "hour" }`.
+    ///
+    /// `per` is `second` (default), `minute` or `hour`. `replace = true` writes the rate instead of the counter.
+    pub rate: Option<Rate>,
+
+    /// Write aggregates of each series per time window instead of every value like `aggregate = { window_seconds = 10, functions = ["mean", "max"] }`.
+    ///
+    /// Functions are `mean`, `min`, `max`, `last` and `count`, all by default. Each writes a field like `power_mean`.
+    pub aggregate: Option<Aggregate>,
+
+    /// Only write values of a series (measurement, tags and field) which changed like `deadband = { absolute = 0.5, heartbeat_seconds = 300 }`.
+    ///
+    /// `relative = 0.01` needs a change of 1 % of the last written value. Without thresholds every change is written.
+    pub deadband: Option<Deadband>,
+}
+
+impl Rule {
+    pub const fn floatify_options(&self) -> floatify::Options<'_> {
+        floatify::Options {
+            states: Some(&self.states),
+            numbers: self.numbers,
+        }
+    }
+
+    pub fn topic_tags(&self) -> bool {
+        self.topic_tags
+            .unwrap_or(self.format != Format::LineProtocol)
+    }
+}
+
+/// Selection and renaming of payload keys.
+///
+/// Keys are the path within the payload joined with `.` like `update.installed_version` or `sensors.0.t`.
+/// Patterns support `*` as a wildcard for any characters like `update.*`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keys {
+    /// When not empty only keys matching one of these patterns are used
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Keys matching one of these patterns are dropped
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Rename a key to another key
+    #[serde(default)]
+    pub rename: HashMap<String, String>,
+
+    /// Values of these keys are added as tags to all the other values of the payload instead of being values themselves.
+    ///
+    /// Helpful for string values like `{"state": "heat", "temp": 21.5}` which can not be stored as a value.
+    /// The tag name is the key, use `rename` to change it.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// Use the value of this member of objects within arrays as key instead of the array index.
+    ///
+    /// With `id` the payload `{"sensors": [{"id": "28-abc", "t": 20.1}]}` results in the key `sensors.28-abc.t` instead of `sensors.0.t`.
+    /// Arrays with elements without this member keep the index of every element.
+    pub array_id: Option<String>,
+}
+
+impl Keys {
+    pub fn is_selected(&self, path: &str) -> bool {
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| wildcard_matches(pattern, path));
+        included
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| wildcard_matches(pattern, path))
+    }
+
+    pub fn renamed(&self, path: &str) -> Option<&str> {
+        self.rename.get(path).map(String::as_str)
+    }
+}
+
+/// Values per key pattern with `*` wildcards in the order of the config file. The first matching pattern wins.
+#[derive(Debug)]
+pub struct Patterns<T>(Vec<(String, T)>);
+
+impl<T> Default for Patterns<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> Patterns<T> {
+    pub fn find(&self, path: &str) -> Option<&T> {
+        self.0
+            .iter()
+            .find(|(pattern, _)| wildcard_matches(pattern, path))
+            .map(|(_, value)| value)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patterns<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for Visitor<T> {
+            type Value = Patterns<T>;
+
+            fn expecting(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                fmt.write_str("a table of key patterns")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut patterns = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    patterns.push(entry);
+                }
+                Ok(Patterns(patterns))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor(std::marker::PhantomData))
+    }
+}
+
+/// Check if the input matches the pattern where `*` matches any amount of characters
+pub fn wildcard_matches(pattern: &str, input: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == input;
+    };
+    let Some(input) = input.strip_prefix(prefix) else {
+        return false;
+    };
+    if rest.is_empty() {
+        return true;
+    }
+    input
+        .char_indices()
+        .map(|(index, _)| index)
+        .chain(std::iter::once(input.len()))
+        .any(|index| wildcard_matches(rest, &input[index..]))
+}
+
+#[test]
+fn patterns_keep_config_order() {
+    let patterns =
+        toml::from_str::<Patterns<u8>>("specific = 1\n\"*\" = 2\n\"spec*\" = 3").unwrap();
+    assert_eq!(patterns.find("specific"), Some(&1));
+    assert_eq!(patterns.find("special"), Some(&2));
+    assert_eq!(patterns.find("other"), Some(&2));
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case("temperature", "temperature", true)]
+#[case("temperature", "temperature_c", false)]
+#[case("update.*", "update.installed_version", true)]
+#[case("update.*", "update", false)]
+#[case("update.*", "updates.foo", false)]
+#[case("*.t", "sensors.0.t", true)]
+#[case("*.t", "sensors.0.temperature", false)]
+#[case("sensors.*.t", "sensors.0.t", true)]
+#[case("*", "whatever", true)]
+#[case("a*b*c", "aXbYc", true)]
+#[case("a*b*c", "aXcYb", false)]
+fn wildcard_matches_works(#[case] pattern: &str, #[case] input: &str, #[case] expected: bool) {
+    assert_eq!(wildcard_matches(pattern, input), expected);
+}
+
+#[test]
+fn parse_example() {
+    let rules = toml::from_str::<Rules>(
+        r#"
+        [[rule]]
+        topic = "zigbee2mqtt/bridge/#"
+        format = "cbor"
+        keys.include = ["nothing"]
+
+        [[rule]]
+        topic = "zigbee2mqtt/+"
+        keys.exclude = ["linkquality", "update.*"]
+        keys.rename = { temperature_c = "temperature" }
+        "#,
+    )
+    .unwrap();
+
+    let rule = rules.get("zigbee2mqtt/sensor");
+    assert_eq!(rule.topic, "zigbee2mqtt/+");
+    assert_eq!(rule.format, Format::Auto);
+    assert!(rule.keys.is_selected("temperature_c"));
+    assert!(!rule.keys.is_selected("linkquality"));
+    assert!(!rule.keys.is_selected("update.installed_version"));
+    assert_eq!(rule.keys.renamed("temperature_c"), Some("temperature"));
+    assert_eq!(rule.keys.renamed("humidity"), None);
+
+    let rule = rules.get("zigbee2mqtt/bridge/state");
+    assert_eq!(rule.topic, "zigbee2mqtt/bridge/#");
+    assert_eq!(rule.format, Format::Cbor);
+    assert!(rules.find("other").is_none());
+    assert!(rules.get("other").keys.is_selected("anything"));
+}
+
+#[test]
+fn states_are_merged() {
+    let rules = toml::from_str::<Rules>(
+        r#"
+        states = { open = 1, closed = 0 }
+
+        [[rule]]
+        topic = "door"
+        states = { closed = -1, locked = 2 }
+        "#,
+    )
+    .unwrap();
+    let rule = rules.get("door");
+    assert_eq!(rule.states.get("open"), Some(1.0));
+    assert_eq!(rule.states.get("closed"), Some(-1.0));
+    assert_eq!(rule.states.get("locked"), Some(2.0));
+    let fallback = rules.get("other");
+    assert_eq!(fallback.states.get("closed"), Some(0.0));
+    assert_eq!(fallback.states.get("locked"), None);
+}
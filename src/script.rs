@@ -0,0 +1,329 @@
+//! Convert payloads with [Rhai](https://rhai.rs/) scripts for formats no built-in decoder covers.
+//!
+//! The script defines `fn convert(topic, payload, time)` with the payload as blob and the receive time in nanoseconds since UNIX epoch.
+//! It returns an array of points like `#{ measurement: "power", tags: #{ phase: "1" }, fields: #{ value: 42.0 }, timestamp: time }`.
+//! `measurement` defaults to `measurement` and `timestamp` to the receive time.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use rhai::{AST, Blob, Dynamic, Engine, INT, Map, Scope};
+use serde::Deserialize;
+
+use crate::message::Message;
+use crate::point::{FieldValue, Point};
+
+/// Scripts are checked for changes at most this often
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    file: PathBuf,
+
+    /// Abort the script when it takes longer
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+}
+
+const fn default_timeout_ms() -> u64 {
+    100
+}
+
+/// Sandboxed Rhai script which is reloaded when the file changes
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "Config")]
+pub struct Script {
+    file: PathBuf,
+    timeout: Duration,
+    engine: Engine,
+    deadline: Arc<Mutex<Instant>>,
+    compiled: RefCell<Compiled>,
+}
+
+#[derive(Debug)]
+struct Compiled {
+    ast: AST,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl TryFrom<Config> for Script {
+    type Error = String;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        let deadline = Arc::new(Mutex::new(Instant::now()));
+        let engine = sandboxed_engine(Arc::clone(&deadline));
+        let modified = modified(&config.file);
+        let ast = engine
+            .compile_file(config.file.clone())
+            .map_err(|err| format!("{}: {err}", config.file.display()))?;
+        Ok(Self {
+            file: config.file,
+            timeout: Duration::from_millis(config.timeout_ms),
+            engine,
+            deadline,
+            compiled: RefCell::new(Compiled {
+                ast,
+                modified,
+                last_check: Instant::now(),
+            }),
+        })
+    }
+}
+
+/// Engine without access to the file system and with limits on time and memory
+fn sandboxed_engine(deadline: Arc<Mutex<Instant>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.set_max_operations(10_000_000);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(1024 * 1024);
+    engine.set_max_array_size(100_000);
+    engine.set_max_map_size(10_000);
+    engine.on_progress(move |_| {
+        let deadline = *deadline.lock().unwrap();
+        (Instant::now() > deadline).then(|| "timeout".into())
+    });
+    engine.on_print(|text| eprintln!("Script: {text}"));
+    engine
+}
+
+fn modified(file: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+impl Script {
+    /// Compile the script again when the file was modified. Keeps the old script when it fails.
+    fn reload_if_changed(&self) {
+        let mut compiled = self.compiled.borrow_mut();
+        compiled.last_check = Instant::now();
+        let modified = modified(&self.file);
+        if modified == compiled.modified {
+            return;
+        }
+        compiled.modified = modified;
+        match self.engine.compile_file(self.file.clone()) {
+            Ok(ast) => {
+                eprintln!("Script {} reloaded", self.file.display());
+                compiled.ast = ast;
+            }
+            Err(err) => eprintln!("Script {} reload failed: {err}", self.file.display()),
+        }
+    }
+
+    pub fn convert(&self, message: &Message) -> Vec<Point> {
+        if self.compiled.borrow().last_check.elapsed() > RELOAD_CHECK_INTERVAL {
+            self.reload_if_changed();
+        }
+        *self.deadline.lock().unwrap() = Instant::now() + self.timeout;
+        let nanos = INT::try_from(message.nanos()).unwrap_or(INT::MAX);
+        let result = self.engine.call_fn::<Dynamic>(
+            &mut Scope::new(),
+            &self.compiled.borrow().ast,
+            "convert",
+            (
+                message.topic().to_owned(),
+                Blob::from(message.payload()),
+                nanos,
+            ),
+        );
+        let points = result
+            .map_err(|err| err.to_string())
+            .and_then(|result| {
+                result
+                    .into_typed_array::<Map>()
+                    .map_err(|type_name| format!("expected an array of maps, got {type_name}"))
+            })
+            .and_then(|points| {
+                points
+                    .into_iter()
+                    .map(|point| into_point(point, message.nanos()))
+                    .collect()
+            });
+        match points {
+            Ok(points) => points,
+            Err(err) => {
+                eprintln!(
+                    "Script {} failed for {}: {err}",
+                    self.file.display(),
+                    message.topic()
+                );
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn into_point(mut map: Map, nanos: u128) -> Result<Point, String> {
+    let measurement = map
+        .remove("measurement")
+        .map_or_else(|| Ok("measurement".to_owned()), Dynamic::into_string)
+        .map_err(|type_name| format!("measurement must be a string, got {type_name}"))?;
+    let timestamp = match map.remove("timestamp") {
+        Some(timestamp) => timestamp
+            .as_int()
+            .ok()
+            .and_then(|timestamp| u128::try_from(timestamp).ok())
+            .ok_or("timestamp must be a positive integer")?,
+        None => nanos,
+    };
+    let mut point = Point::new(measurement, timestamp);
+    if let Some(tags) = map.remove("tags") {
+        let tags = tags.try_cast::<Map>().ok_or("tags must be a map")?;
+        point.tags = tags
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+    }
+    let fields = map
+        .remove("fields")
+        .and_then(Dynamic::try_cast::<Map>)
+        .ok_or("fields must be a map")?;
+    for (key, value) in fields {
+        let value = if let Ok(float) = value.as_float() {
+            FieldValue::Float(float)
+        } else if let Ok(int) = value.as_int() {
+            // Same type as all the other values to prevent field type conflicts
+            #[expect(clippy::cast_precision_loss)]
+            FieldValue::Float(int as f64)
+        } else if let Ok(bool) = value.as_bool() {
+            FieldValue::Boolean(bool)
+        } else if value.is_string() {
+            FieldValue::String(value.to_string())
+        } else {
+            return Err(format!(
+                "field {key} has unsupported type {}",
+                value.type_name()
+            ));
+        };
+        point.fields.push((key.to_string(), value));
+    }
+    if point.fields.is_empty() {
+        return Err("point has no fields".to_owned());
+    }
+    if let Some(key) = map.keys().next() {
+        return Err(format!("unknown key {key}"));
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    const SCRIPT: &str = r#"
+        fn convert(topic, payload, time) {
+            let text = payload.as_string();
+            let parts = text.split("|");
+            [
+                #{ measurement: "power", tags: #{ device: topic }, fields: #{ value: parse_float(parts[0]) } },
+                #{ fields: #{ on: parts[1] == "1", count: 3 }, timestamp: 42 },
+            ]
+        }
+    "#;
+
+    struct TempScript {
+        _dir: TempDir,
+        file: PathBuf,
+    }
+
+    impl TempScript {
+        fn new(test: &str, content: &str) -> Self {
+            let dir = TempDir::new(&format!("script-{test}"));
+            let file = dir.write("script.rhai", content);
+            Self { _dir: dir, file }
+        }
+
+        fn load(&self, timeout_ms: u64) -> Result<Script, String> {
+            Script::try_from(Config {
+                file: self.file.clone(),
+                timeout_ms,
+            })
+        }
+    }
+
+    fn convert(script: &Script, payload: &str) -> Vec<String> {
+        let message = Message::new(1337, "meter/1".into(), payload.into());
+        script
+            .convert(&message)
+            .iter()
+            .map(crate::line_protocol::render)
+            .collect()
+    }
+
+    #[test]
+    fn convert_works() {
+        let temp = TempScript::new("convert", SCRIPT);
+        let script = temp.load(100).unwrap();
+        assert_eq!(
+            convert(&script, "42.5|1"),
+            [
+                "power,device=meter/1 value=42.5 1337",
+                "measurement count=3,on=true 42",
+            ]
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::runtime_error(r#"fn convert(topic, payload, time) { throw "nope"; }"#)]
+    #[case::not_an_array("fn convert(topic, payload, time) { 42 }")]
+    #[case::no_fields(r#"fn convert(topic, payload, time) { [#{ measurement: "m" }] }"#)]
+    #[case::unknown_key("fn convert(topic, payload, time) { [#{ fields: #{ a: 1 }, other: 1 }] }")]
+    #[case::negative_timestamp(
+        "fn convert(topic, payload, time) { [#{ fields: #{ a: 1 }, timestamp: -1 }] }"
+    )]
+    #[case::endless_loop("fn convert(topic, payload, time) { loop {} }")]
+    #[case::huge_string(
+        r#"fn convert(topic, payload, time) { let text = "a"; loop { text += text; } }"#
+    )]
+    #[case::import(r#"import "other" as other; fn convert(topic, payload, time) { [] }"#)]
+    fn convert_fails(#[case] content: &str) {
+        let temp = TempScript::new(&format!("fails-{}", content.len()), content);
+        let script = temp.load(50).unwrap();
+        assert_eq!(convert(&script, ""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn invalid_script_fails() {
+        let temp = TempScript::new("invalid", "fn convert(");
+        assert!(temp.load(100).is_err());
+    }
+
+    #[test]
+    fn reload_works() {
+        let temp = TempScript::new("reload", SCRIPT);
+        let script = temp.load(100).unwrap();
+
+        // Invalid changes keep the old script
+        std::fs::write(&temp.file, "fn convert(").unwrap();
+        set_modified(&temp.file, 1);
+        script.reload_if_changed();
+        assert_eq!(convert(&script, "1|0").len(), 2);
+
+        std::fs::write(
+            &temp.file,
+            "fn convert(topic, payload, time) { [#{ fields: #{ a: 1.5 } }] }",
+        )
+        .unwrap();
+        set_modified(&temp.file, 2);
+        script.reload_if_changed();
+        assert_eq!(convert(&script, "1|0"), ["measurement a=1.5 1337"]);
+    }
+
+    /// File systems might have a low resolution of the modification time
+    fn set_modified(file: &std::path::Path, seconds: u64) {
+        std::fs::File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+            .unwrap();
+    }
+}
@@ -0,0 +1,440 @@
+//! [Sparkplug B](https://sparkplug.eclipse.org/) messages of industrial edge nodes and their devices.
+//!
+//! Topics look like `spBv1.0/{group}/{type}/{edge_node}/{device}` with a protobuf payload containing metrics.
+//! BIRTH messages announce the metric names together with aliases. DATA messages may only contain the alias.
+
+use std::collections::HashMap;
+
+use protobuf::CodedInputStream;
+use protobuf::rt::WireType;
+
+use crate::floatify::{self, floatify};
+use crate::message::Message;
+use crate::point::Point;
+
+/// Edge node or device of an edge node
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Id {
+    group: String,
+    edge_node: String,
+    device: Option<String>,
+}
+
+impl Id {
+    fn tags(&self) -> Vec<(String, String)> {
+        let mut tags = vec![
+            ("group".to_owned(), self.group.clone()),
+            ("edge_node".to_owned(), self.edge_node.clone()),
+        ];
+        if let Some(device) = &self.device {
+            tags.push(("device".to_owned(), device.clone()));
+        }
+        tags
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Birth,
+    Death,
+    Data,
+}
+
+/// Parse `spBv1.0/{group}/{type}/{edge_node}` and `spBv1.0/{group}/{type}/{edge_node}/{device}`.
+///
+/// Commands and host application states are not relevant and ignored.
+fn parse_topic(topic: &str) -> Option<(Kind, Id)> {
+    let mut parts = topic.split('/');
+    if parts.next()? != "spBv1.0" {
+        return None;
+    }
+    let group = parts.next()?;
+    let kind = parts.next()?;
+    let edge_node = parts.next()?;
+    let device = parts.next();
+    if parts.next().is_some() {
+        return None;
+    }
+    let kind = match (kind, device.is_some()) {
+        ("NBIRTH", false) | ("DBIRTH", true) => Kind::Birth,
+        ("NDEATH", false) | ("DDEATH", true) => Kind::Death,
+        ("NDATA", false) | ("DDATA", true) => Kind::Data,
+        _ => return None,
+    };
+    let id = Id {
+        group: group.to_owned(),
+        edge_node: edge_node.to_owned(),
+        device: device.map(ToOwned::to_owned),
+    };
+    Some((kind, id))
+}
+
+/// Tracks the metric aliases announced by BIRTH messages
+#[derive(Debug, Default)]
+pub struct Sparkplug {
+    aliases: HashMap<Id, HashMap<u64, String>>,
+}
+
+impl Sparkplug {
+    /// Points of the metrics with `group`, `edge_node`, `device` and `metric` tags.
+    ///
+    /// BIRTH and DEATH messages additionally create an `online` field of the node or device.
+    pub fn process(&mut self, message: &Message, options: floatify::Options<'_>) -> Vec<Point> {
+        let Some((kind, id)) = parse_topic(message.topic()) else {
+            return Vec::new();
+        };
+        let Some(payload) = decode_payload(message.payload(), options) else {
+            return Vec::new();
+        };
+        let nanos = payload
+            .timestamp
+            .map_or_else(|| message.nanos(), millis_to_nanos);
+        let online = |nanos: u128, online: bool| {
+            let mut point = Point::new("measurement", nanos);
+            point.tags = id.tags();
+            point.add_field("online", if online { 1.0 } else { 0.0 });
+            point
+        };
+        match kind {
+            Kind::Birth => {
+                let aliases = payload
+                    .metrics
+                    .iter()
+                    .filter_map(|metric| Some((metric.alias?, metric.name.clone()?)))
+                    .collect();
+                self.aliases.insert(id.clone(), aliases);
+                let mut points = vec![online(nanos, true)];
+                points.extend(self.metric_points(&id, nanos, &payload.metrics));
+                points
+            }
+            Kind::Death => {
+                if id.device.is_some() {
+                    self.aliases.remove(&id);
+                } else {
+                    // The devices of an edge node are offline with their edge node
+                    self.aliases.retain(|other, _| {
+                        other.group != id.group || other.edge_node != id.edge_node
+                    });
+                }
+                // The NDEATH is the will message set on connect, its timestamp is outdated
+                vec![online(message.nanos(), false)]
+            }
+            Kind::Data => self.metric_points(&id, nanos, &payload.metrics),
+        }
+    }
+
+    fn metric_points(&self, id: &Id, nanos: u128, metrics: &[Metric]) -> Vec<Point> {
+        let aliases = self.aliases.get(id);
+        metrics
+            .iter()
+            .filter_map(|metric| {
+                let name = metric
+                    .name
+                    .as_ref()
+                    .or_else(|| aliases?.get(&metric.alias?))?;
+                let value = metric.value?;
+                let mut point = Point::new(
+                    "measurement",
+                    metric.timestamp.map_or(nanos, millis_to_nanos),
+                );
+                point.tags = id.tags();
+                point.tags.push(("metric".to_owned(), name.clone()));
+                point.add_field("value", value);
+                Some(point)
+            })
+            .collect()
+    }
+}
+
+fn millis_to_nanos(millis: u64) -> u128 {
+    u128::from(millis) * 1_000_000
+}
+
+/// Relevant parts of `org.eclipse.tahu.protobuf.Payload`
+#[derive(Debug, Default, PartialEq)]
+struct Payload {
+    /// Milliseconds since UNIX epoch
+    timestamp: Option<u64>,
+    metrics: Vec<Metric>,
+}
+
+/// Relevant parts of `org.eclipse.tahu.protobuf.Payload.Metric`
+#[derive(Debug, Default, PartialEq)]
+struct Metric {
+    name: Option<String>,
+    alias: Option<u64>,
+    /// Milliseconds since UNIX epoch
+    timestamp: Option<u64>,
+    value: Option<f64>,
+}
+
+/// Tags are the field number shifted by 3 combined with the wire type
+mod tag {
+    pub const PAYLOAD_TIMESTAMP: u32 = 1 << 3;
+    pub const PAYLOAD_METRICS: u32 = 2 << 3 | 2;
+
+    pub const NAME: u32 = 1 << 3 | 2;
+    pub const ALIAS: u32 = 2 << 3;
+    pub const TIMESTAMP: u32 = 3 << 3;
+    pub const DATATYPE: u32 = 4 << 3;
+    pub const IS_NULL: u32 = 7 << 3;
+    pub const INT_VALUE: u32 = 10 << 3;
+    pub const LONG_VALUE: u32 = 11 << 3;
+    pub const FLOAT_VALUE: u32 = 12 << 3 | 5;
+    pub const DOUBLE_VALUE: u32 = 13 << 3 | 1;
+    pub const BOOLEAN_VALUE: u32 = 14 << 3;
+    pub const STRING_VALUE: u32 = 15 << 3 | 2;
+}
+
+fn skip(input: &mut CodedInputStream<'_>, tag: u32) -> Option<()> {
+    input.skip_field(WireType::new(tag & 7)?).ok()
+}
+
+fn decode_payload(bytes: &[u8], options: floatify::Options<'_>) -> Option<Payload> {
+    let mut input = CodedInputStream::from_bytes(bytes);
+    let mut payload = Payload::default();
+    while let Some(tag) = input.read_raw_tag_or_eof().ok()? {
+        match tag {
+            tag::PAYLOAD_TIMESTAMP => payload.timestamp = Some(input.read_uint64().ok()?),
+            tag::PAYLOAD_METRICS => {
+                payload
+                    .metrics
+                    .push(decode_metric(&input.read_bytes().ok()?, options)?);
+            }
+            _ => skip(&mut input, tag)?,
+        }
+    }
+    Some(payload)
+}
+
+#[expect(clippy::cast_precision_loss)]
+fn decode_metric(bytes: &[u8], options: floatify::Options<'_>) -> Option<Metric> {
+    let mut input = CodedInputStream::from_bytes(bytes);
+    let mut metric = Metric::default();
+    let mut datatype = 0;
+    let mut is_null = false;
+    let mut int = None;
+    let mut long = None;
+    while let Some(tag) = input.read_raw_tag_or_eof().ok()? {
+        match tag {
+            tag::NAME => metric.name = Some(input.read_string().ok()?),
+            tag::ALIAS => metric.alias = Some(input.read_uint64().ok()?),
+            tag::TIMESTAMP => metric.timestamp = Some(input.read_uint64().ok()?),
+            tag::DATATYPE => datatype = input.read_uint32().ok()?,
+            tag::IS_NULL => is_null = input.read_bool().ok()?,
+            tag::INT_VALUE => int = Some(input.read_uint32().ok()?),
+            tag::LONG_VALUE => long = Some(input.read_uint64().ok()?),
+            tag::FLOAT_VALUE => metric.value = Some(f64::from(input.read_float().ok()?)),
+            tag::DOUBLE_VALUE => metric.value = Some(input.read_double().ok()?),
+            tag::BOOLEAN_VALUE => {
+                metric.value = Some(if input.read_bool().ok()? { 1.0 } else { 0.0 });
+            }
+            tag::STRING_VALUE => metric.value = floatify(&input.read_string().ok()?, options),
+            _ => skip(&mut input, tag)?,
+        }
+    }
+    // Signed integers are transmitted as two's complement in the unsigned fields
+    match datatype {
+        1..=3 => metric.value = int.map(|int| f64::from(int.cast_signed())),
+        5 | 6 => metric.value = int.map(f64::from),
+        // UInt32 is in `long_value` with Eclipse Tahu and most other encoders
+        7 => metric.value = long.map(|long| long as f64).or_else(|| int.map(f64::from)),
+        4 => metric.value = long.map(|long| long.cast_signed() as f64),
+        8 => metric.value = long.map(|long| long as f64),
+        _ => {}
+    }
+    if is_null {
+        metric.value = None;
+    }
+    Some(metric)
+}
+
+#[cfg(test)]
+mod tests {
+    use protobuf::CodedOutputStream;
+
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    enum Value {
+        Int32(i32),
+        UInt32(u32),
+        UInt32InLong(u32),
+        Double(f64),
+        Boolean(bool),
+    }
+
+    fn metric(
+        name: Option<&str>,
+        alias: Option<u64>,
+        timestamp: Option<u64>,
+        value: Value,
+    ) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut output = CodedOutputStream::vec(&mut bytes);
+        if let Some(name) = name {
+            output.write_string(1, name).unwrap();
+        }
+        if let Some(alias) = alias {
+            output.write_uint64(2, alias).unwrap();
+        }
+        if let Some(timestamp) = timestamp {
+            output.write_uint64(3, timestamp).unwrap();
+        }
+        match value {
+            Value::Int32(int) => {
+                output.write_uint32(4, 3).unwrap();
+                output.write_uint32(10, int.cast_unsigned()).unwrap();
+            }
+            Value::UInt32(int) => {
+                output.write_uint32(4, 7).unwrap();
+                output.write_uint32(10, int).unwrap();
+            }
+            Value::UInt32InLong(int) => {
+                output.write_uint32(4, 7).unwrap();
+                output.write_uint64(11, u64::from(int)).unwrap();
+            }
+            Value::Double(double) => {
+                output.write_uint32(4, 10).unwrap();
+                output.write_double(13, double).unwrap();
+            }
+            Value::Boolean(bool) => {
+                output.write_uint32(4, 11).unwrap();
+                output.write_bool(14, bool).unwrap();
+            }
+        }
+        output.flush().unwrap();
+        drop(output);
+        bytes
+    }
+
+    fn payload(timestamp: u64, metrics: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut output = CodedOutputStream::vec(&mut bytes);
+        output.write_uint64(1, timestamp).unwrap();
+        for metric in metrics {
+            output.write_bytes(2, metric).unwrap();
+        }
+        output.write_uint64(3, 0).unwrap(); // seq
+        output.flush().unwrap();
+        drop(output);
+        bytes
+    }
+
+    fn process(sparkplug: &mut Sparkplug, topic: &str, payload: Vec<u8>) -> Vec<String> {
+        let message = Message::new(1337, topic.to_owned(), payload);
+        sparkplug
+            .process(&message, floatify::Options::default())
+            .iter()
+            .map(crate::line_protocol::render)
+            .collect()
+    }
+
+    #[rstest::rstest]
+    #[case("spBv1.0/plant/NDATA/edge", Some(Kind::Data), None)]
+    #[case("spBv1.0/plant/DBIRTH/edge/press", Some(Kind::Birth), Some("press"))]
+    #[case("spBv1.0/plant/DDEATH/edge/press", Some(Kind::Death), Some("press"))]
+    #[case("spBv1.0/plant/DDATA/edge", None, None)]
+    #[case("spBv1.0/plant/NDATA/edge/press", None, None)]
+    #[case("spBv1.0/plant/NCMD/edge", None, None)]
+    #[case("spBv1.0/STATE/host", None, None)]
+    #[case("spAv1.0/plant/NDATA/edge", None, None)]
+    fn parse_topic_works(
+        #[case] topic: &str,
+        #[case] kind: Option<Kind>,
+        #[case] device: Option<&str>,
+    ) {
+        let parsed = parse_topic(topic);
+        assert_eq!(parsed.as_ref().map(|(kind, _)| *kind), kind);
+        if let Some((_, id)) = parsed {
+            assert_eq!(id.group, "plant");
+            assert_eq!(id.edge_node, "edge");
+            assert_eq!(id.device.as_deref(), device);
+        }
+    }
+
+    #[test]
+    fn decode_signed() {
+        let bytes = payload(42, &[metric(Some("offset"), None, None, Value::Int32(-5))]);
+        let decoded = decode_payload(&bytes, floatify::Options::default()).unwrap();
+        assert_eq!(decoded.timestamp, Some(42));
+        assert_eq!(decoded.metrics[0].value, Some(-5.0));
+    }
+
+    #[rstest::rstest]
+    #[case::long_value(Value::UInt32InLong(4_000_000_000))]
+    #[case::int_value(Value::UInt32(4_000_000_000))]
+    fn decode_uint32(#[case] value: Value) {
+        let bytes = payload(42, &[metric(Some("counter"), None, None, value)]);
+        let decoded = decode_payload(&bytes, floatify::Options::default()).unwrap();
+        assert_eq!(decoded.metrics[0].value, Some(4_000_000_000.0));
+    }
+
+    #[test]
+    fn decode_invalid_fails() {
+        assert_eq!(
+            decode_payload(&[0xff, 0xff, 0xff], floatify::Options::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn aliases_of_birth_are_used() {
+        let mut sparkplug = Sparkplug::default();
+        let birth = payload(
+            1000,
+            &[
+                metric(Some("temperature"), Some(1), None, Value::Double(20.5)),
+                metric(Some("running"), Some(2), None, Value::Boolean(true)),
+            ],
+        );
+        assert_eq!(
+            process(&mut sparkplug, "spBv1.0/plant/DBIRTH/edge/press", birth),
+            [
+                "measurement,group=plant,edge_node=edge,device=press online=1 1000000000",
+                "measurement,group=plant,edge_node=edge,device=press,metric=temperature value=20.5 1000000000",
+                "measurement,group=plant,edge_node=edge,device=press,metric=running value=1 1000000000",
+            ]
+        );
+
+        let data = payload(
+            2000,
+            &[
+                metric(None, Some(1), Some(1500), Value::Double(21.0)),
+                metric(None, Some(2), None, Value::Boolean(false)),
+                metric(None, Some(3), None, Value::Double(1.0)),
+            ],
+        );
+        assert_eq!(
+            process(
+                &mut sparkplug,
+                "spBv1.0/plant/DDATA/edge/press",
+                data.clone()
+            ),
+            [
+                "measurement,group=plant,edge_node=edge,device=press,metric=temperature value=21 1500000000",
+                "measurement,group=plant,edge_node=edge,device=press,metric=running value=0 2000000000",
+            ]
+        );
+
+        // Aliases are per device
+        assert_eq!(
+            process(
+                &mut sparkplug,
+                "spBv1.0/plant/DDATA/edge/other",
+                data.clone()
+            ),
+            Vec::<String>::new()
+        );
+
+        // Death of the edge node also forgets the aliases of its devices
+        assert_eq!(
+            process(&mut sparkplug, "spBv1.0/plant/NDEATH/edge", payload(0, &[])),
+            ["measurement,group=plant,edge_node=edge online=0 1337"]
+        );
+        assert_eq!(
+            process(&mut sparkplug, "spBv1.0/plant/DDATA/edge/press", data),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Fixtures shared by the tests of multiple modules
+
+use std::path::PathBuf;
+
+use crate::line_protocol;
+use crate::point::{FieldValue, Point, seconds_to_nanos};
+
+pub const SECOND: u128 = seconds_to_nanos(1);
+
+/// Point of the `meter` topic at the given seconds since UNIX epoch with a single field
+pub fn point(seconds: u128, key: &str, value: impl Into<FieldValue>) -> Point {
+    let mut point = Point::new("measurement", seconds * SECOND);
+    point.tags.push(("topic".to_owned(), "meter".to_owned()));
+    point.fields.push((key.to_owned(), value.into()));
+    point
+}
+
+pub fn render(points: &[Point]) -> Vec<String> {
+    points.iter().map(line_protocol::render).collect()
+}
+
+/// Temporary directory named by the test to not collide with other tests. Removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(test: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("mqtt2influxdb-{}-{test}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Write a file into the directory and return its path
+    pub fn write(&self, name: &str, content: &str) -> PathBuf {
+        let file = self.path(name);
+        std::fs::write(&file, content).unwrap();
+        file
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
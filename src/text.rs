@@ -0,0 +1,102 @@
+//! Plain text payloads with multiple values like `t=21.3 h=40` or `21.3;40`.
+//!
+//! They are parsed into JSON objects of strings so they are flattened the same way as JSON payloads.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::floatify::Numbers;
+
+/// Parse `key=value` pairs separated by whitespace, `,` or `;` like `t=21.3 h=40 p=1013`.
+///
+/// `,` does not separate pairs when it is the decimal or thousands separator of the `numbers` like in `t=21,5 h=40`.
+/// Parts without `=` are ignored.
+pub fn key_value(payload: &str, numbers: Numbers) -> Value {
+    let comma = numbers.decimal != ',' && numbers.thousands != Some(',');
+    payload
+        .split(|char: char| char.is_whitespace() || (comma && char == ',') || char == ';')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_owned(), Value::String(value.to_owned())))
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+/// Single line of comma separated values with the given column names
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Csv {
+    /// Name of each column. Columns with an empty name and additional columns are ignored.
+    pub columns: Vec<String>,
+
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+}
+
+const fn default_delimiter() -> char {
+    ','
+}
+
+impl Csv {
+    pub fn parse(&self, payload: &str) -> Value {
+        let line = payload.lines().next().unwrap_or_default();
+        self.columns
+            .iter()
+            .zip(line.split(self.delimiter))
+            .filter(|(column, _)| !column.is_empty())
+            .map(|(column, value)| {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+                (column.clone(), Value::String(value.to_owned()))
+            })
+            .collect::<Map<_, _>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[rstest::rstest]
+    #[case("t=21.3 h=40 p=1013", json!({"t": "21.3", "h": "40", "p": "1013"}))]
+    #[case("t=21.3,h=40;p=1013\n", json!({"t": "21.3", "h": "40", "p": "1013"}))]
+    #[case("state=on  rssi=-70 whatever", json!({"state": "on", "rssi": "-70"}))]
+    #[case("=42 a=", json!({"a": ""}))]
+    #[case("", json!({}))]
+    fn key_value_works(#[case] payload: &str, #[case] expected: Value) {
+        assert_eq!(key_value(payload, Numbers::default()), expected);
+    }
+
+    #[test]
+    fn key_value_decimal_comma() {
+        let numbers = toml::from_str::<Numbers>(r#"decimal = ",""#).unwrap();
+        assert_eq!(
+            key_value("t=21,5 h=40;p=1013", numbers),
+            json!({"t": "21,5", "h": "40", "p": "1013"})
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(';', "21.3;40;1013", json!({"t": "21.3", "h": "40", "p": "1013"}))]
+    #[case(',', "21.3, 40, 1013, 5\n1,2,3", json!({"t": "21.3", "h": "40", "p": "1013"}))]
+    #[case(',', "\"21.3\",40", json!({"t": "21.3", "h": "40"}))]
+    fn csv_works(#[case] delimiter: char, #[case] payload: &str, #[case] expected: Value) {
+        let csv = Csv {
+            columns: vec!["t".into(), "h".into(), "p".into()],
+            delimiter,
+        };
+        assert_eq!(csv.parse(payload), expected);
+    }
+
+    #[test]
+    fn csv_skips_unnamed_columns() {
+        let csv = toml::from_str::<Csv>(r#"columns = ["", "t"]"#).unwrap();
+        assert_eq!(csv.parse("id,21.3"), json!({"t": "21.3"}));
+    }
+}
@@ -0,0 +1,236 @@
+//! Handle timestamps far off the receive time like 1970 from devices without a synchronized clock
+
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::line_protocol;
+use crate::point::{Point, seconds_to_nanos};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Use the receive time instead
+    #[default]
+    Clamp,
+    /// Write the point to the dead letter file instead of the output
+    Reject,
+    /// Keep the timestamp and add a `timestamp_outside` tag with `past` or `future`
+    Tag,
+}
+
+/// Accepted timestamps relative to the receive time and what happens to the others
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimestampGuard {
+    /// Accept timestamps at most this much before the receive time. Unlimited when missing.
+    pub past_seconds: Option<u64>,
+
+    /// Accept timestamps at most this much after the receive time. Unlimited when missing.
+    pub future_seconds: Option<u64>,
+
+    #[serde(default)]
+    pub action: Action,
+
+    /// Append rejected points as line protocol to this file instead of stderr
+    pub dead_letter: Option<PathBuf>,
+}
+
+impl TimestampGuard {
+    fn outside(&self, received: u128, timestamp: u128) -> Option<&'static str> {
+        if self
+            .past_seconds
+            .is_some_and(|past| timestamp < received.saturating_sub(seconds_to_nanos(past)))
+        {
+            Some("past")
+        } else if self
+            .future_seconds
+            .is_some_and(|future| timestamp > received.saturating_add(seconds_to_nanos(future)))
+        {
+            Some("future")
+        } else {
+            None
+        }
+    }
+}
+
+/// Amount of points outside the accepted timestamps per action
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Outcomes {
+    pub clamped: u64,
+    pub rejected: u64,
+    pub tagged: u64,
+}
+
+impl Outcomes {
+    pub const fn is_empty(self) -> bool {
+        self.clamped == 0 && self.rejected == 0 && self.tagged == 0
+    }
+
+    /// Handle the points with timestamps outside of the accepted ones of the guard
+    pub fn guard(
+        &mut self,
+        guard: &TimestampGuard,
+        received: u128,
+        points: Vec<Point>,
+    ) -> Vec<Point> {
+        let mut accepted = Vec::with_capacity(points.len());
+        let mut rejected = Vec::new();
+        for mut point in points {
+            let Some(outside) = guard.outside(received, point.timestamp) else {
+                accepted.push(point);
+                continue;
+            };
+            match guard.action {
+                Action::Clamp => {
+                    self.clamped = self.clamped.saturating_add(1);
+                    point.timestamp = received;
+                    accepted.push(point);
+                }
+                Action::Reject => {
+                    self.rejected = self.rejected.saturating_add(1);
+                    rejected.push(point);
+                }
+                Action::Tag => {
+                    self.tagged = self.tagged.saturating_add(1);
+                    point
+                        .tags
+                        .push(("timestamp_outside".to_owned(), outside.to_owned()));
+                    accepted.push(point);
+                }
+            }
+        }
+        if !rejected.is_empty() {
+            dead_letter(guard.dead_letter.as_ref(), &rejected);
+        }
+        accepted
+    }
+}
+
+impl std::fmt::Display for Outcomes {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{} clamped, {} rejected, {} tagged",
+            self.clamped, self.rejected, self.tagged
+        )
+    }
+}
+
+fn dead_letter(file: Option<&PathBuf>, points: &[Point]) {
+    let lines = points
+        .iter()
+        .map(|point| line_protocol::render(point) + "\n")
+        .collect::<String>();
+    let Some(file) = file else {
+        eprint!("Timestamp rejected: {lines}");
+        return;
+    };
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .and_then(|mut writer| writer.write_all(lines.as_bytes()));
+    if let Err(err) = result {
+        eprintln!("Dead letter {} failed: {err}", file.display());
+        eprint!("Timestamp rejected: {lines}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{SECOND, TempDir, point, render};
+
+    const RECEIVED: u128 = 1_000 * SECOND;
+
+    fn guard(action: Action) -> TimestampGuard {
+        TimestampGuard {
+            past_seconds: Some(60),
+            future_seconds: Some(10),
+            action,
+            dead_letter: None,
+        }
+    }
+
+    fn points() -> Vec<Point> {
+        [0, 939, 940, 1010, 1011]
+            .map(|seconds| point(seconds, "value", 1.0))
+            .to_vec()
+    }
+
+    #[test]
+    fn clamp() {
+        let mut outcomes = Outcomes::default();
+        let points = outcomes.guard(&guard(Action::Clamp), RECEIVED, points());
+        assert_eq!(
+            render(&points),
+            [
+                "measurement,topic=meter value=1 1000000000000",
+                "measurement,topic=meter value=1 1000000000000",
+                "measurement,topic=meter value=1 940000000000",
+                "measurement,topic=meter value=1 1010000000000",
+                "measurement,topic=meter value=1 1000000000000",
+            ]
+        );
+        assert_eq!(
+            outcomes,
+            Outcomes {
+                clamped: 3,
+                ..Outcomes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reject() {
+        let dir = TempDir::new("dead-letter");
+        let file = dir.path("rejected.lp");
+        let guard = TimestampGuard {
+            dead_letter: Some(file.clone()),
+            ..guard(Action::Reject)
+        };
+        let mut outcomes = Outcomes::default();
+        let points = outcomes.guard(&guard, RECEIVED, points());
+        let dead_letter = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(
+            render(&points),
+            [
+                "measurement,topic=meter value=1 940000000000",
+                "measurement,topic=meter value=1 1010000000000",
+            ]
+        );
+        assert_eq!(
+            dead_letter,
+            "measurement,topic=meter value=1 0\nmeasurement,topic=meter value=1 939000000000\nmeasurement,topic=meter value=1 1011000000000\n"
+        );
+        assert_eq!(outcomes.to_string(), "0 clamped, 3 rejected, 0 tagged");
+    }
+
+    #[test]
+    fn tag() {
+        let mut outcomes = Outcomes::default();
+        let points = outcomes.guard(&guard(Action::Tag), RECEIVED, points());
+        assert_eq!(
+            render(&points),
+            [
+                "measurement,topic=meter,timestamp_outside=past value=1 0",
+                "measurement,topic=meter,timestamp_outside=past value=1 939000000000",
+                "measurement,topic=meter value=1 940000000000",
+                "measurement,topic=meter value=1 1010000000000",
+                "measurement,topic=meter,timestamp_outside=future value=1 1011000000000",
+            ]
+        );
+        assert_eq!(outcomes.tagged, 3);
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let mut outcomes = Outcomes::default();
+        let points = outcomes.guard(&TimestampGuard::default(), RECEIVED, points());
+        assert_eq!(points.len(), 5);
+        assert!(outcomes.is_empty());
+    }
+}
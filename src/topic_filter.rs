@@ -0,0 +1,64 @@
+/// Check if the MQTT topic is matched by the MQTT topic filter which might contain the wildcards `+` and `#`.
+/// Shared subscriptions like `$share/group/foo/#` match like their filter `foo/#`.
+///
+/// See <https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901241>
+pub fn matches(filter: &str, topic: &str) -> bool {
+    let filter = without_share(filter);
+    // Wildcards on the first level do not match topics starting with $
+    if topic.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+    let mut topic = topic.split('/');
+    for filter in filter.split('/') {
+        match (filter, topic.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (filter, Some(topic)) if filter == topic => {}
+            _ => return false,
+        }
+    }
+    topic.next().is_none()
+}
+
+/// Topic filter of a shared subscription `$share/{ShareName}/{filter}`
+///
+/// See <https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901250>
+fn without_share(filter: &str) -> &str {
+    filter
+        .strip_prefix("$share/")
+        .and_then(|shared| shared.split_once('/'))
+        .map_or(filter, |(_group, filter)| filter)
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case("foo/bar", "foo/bar")]
+#[case("foo/+", "foo/bar")]
+#[case("+/bar", "foo/bar")]
+#[case("+/+", "foo/bar")]
+#[case("foo/+", "foo/")]
+#[case("#", "foo/bar")]
+#[case("foo/#", "foo/bar/baz")]
+#[case("foo/#", "foo")]
+#[case("$SYS/#", "$SYS/broker")]
+#[case("$share/group/sensors/#", "sensors/x")]
+#[case("$share/group/+/x", "sensors/x")]
+fn test_matches(#[case] filter: &str, #[case] topic: &str) {
+    assert!(matches(filter, topic));
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+#[case("foo/bar", "foo/baz")]
+#[case("foo/bar", "foo/bar/baz")]
+#[case("foo/+", "foo/bar/baz")]
+#[case("foo/+", "foo")]
+#[case("+/+", "foo")]
+#[case("foo/#", "bar/foo")]
+#[case("#", "$SYS/broker")]
+#[case("+/broker", "$SYS/broker")]
+#[case("$share/group/sensors/#", "other/x")]
+#[case("$share/group/#", "$SYS/broker")]
+fn test_not_matches(#[case] filter: &str, #[case] topic: &str) {
+    assert!(!matches(filter, topic));
+}
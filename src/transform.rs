@@ -0,0 +1,162 @@
+//! Transform values before they are written like `value * 0.1 - 40` or `clamp(value, 0, 100)`
+
+use evalexpr::{Context, EvalexprError, EvalexprResult, Node, Value};
+use serde::Deserialize;
+
+use crate::rules::Patterns;
+
+/// Transformation of the values of matching keys
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Transforms(Patterns<Transform>);
+
+impl Transforms {
+    /// Transformed value or `None` when the transformation fails
+    pub fn apply(&self, path: &str, value: f64) -> Option<f64> {
+        self.0
+            .find(path)
+            .map_or(Some(value), |transform| transform.apply(value))
+            .filter(|value| value.is_finite())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "Config")]
+pub enum Transform {
+    Linear { scale: f64, offset: f64 },
+    Expression(Node),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum Config {
+    /// `{ scale = 0.1, offset = -40 }`
+    Linear {
+        #[serde(default = "default_scale")]
+        scale: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+    /// `"round(value / 10)"`
+    Expression(String),
+}
+
+const fn default_scale() -> f64 {
+    1.0
+}
+
+impl TryFrom<Config> for Transform {
+    type Error = String;
+
+    fn try_from(config: Config) -> Result<Self, Self::Error> {
+        match config {
+            Config::Linear { scale, offset } => Ok(Self::Linear { scale, offset }),
+            Config::Expression(expression) => evalexpr::build_operator_tree(&expression)
+                .map(Self::Expression)
+                .map_err(|err| format!("invalid expression {expression:?}: {err}")),
+        }
+    }
+}
+
+impl Transform {
+    fn apply(&self, value: f64) -> Option<f64> {
+        match self {
+            Self::Linear { scale, offset } => Some(value.mul_add(*scale, *offset)),
+            Self::Expression(node) => match node.eval_with_context(&Variables(Value::Float(value)))
+            {
+                Ok(Value::Float(float)) => Some(float),
+                #[expect(clippy::cast_precision_loss)]
+                Ok(Value::Int(int)) => Some(int as f64),
+                Ok(Value::Boolean(bool)) => Some(if bool { 1.0 } else { 0.0 }),
+                Ok(_) | Err(_) => None,
+            },
+        }
+    }
+}
+
+/// Provides the `value` to expressions and functions in addition to the built-in ones like `min`, `round` or `if`
+struct Variables(Value);
+
+impl Context for Variables {
+    fn get_value(&self, identifier: &str) -> Option<&Value> {
+        (identifier == "value").then_some(&self.0)
+    }
+
+    fn call_function(&self, identifier: &str, argument: &Value) -> EvalexprResult<Value> {
+        match identifier {
+            "abs" => Ok(Value::Float(argument.as_number()?.abs())),
+            "clamp" => {
+                let arguments = argument.as_fixed_len_tuple(3)?;
+                let [value, min, max] =
+                    [&arguments[0], &arguments[1], &arguments[2]].map(Value::as_number);
+                let (value, min, max) = (value?, min?, max?);
+                if min > max {
+                    return Err(EvalexprError::CustomMessage(
+                        "clamp needs min <= max".to_owned(),
+                    ));
+                }
+                Ok(Value::Float(value.clamp(min, max)))
+            }
+            _ => Err(EvalexprError::FunctionIdentifierNotFound(
+                identifier.to_owned(),
+            )),
+        }
+    }
+
+    fn are_builtin_functions_disabled(&self) -> bool {
+        false
+    }
+
+    fn set_builtin_functions_disabled(&mut self, _disabled: bool) -> EvalexprResult<()> {
+        Err(EvalexprError::BuiltinFunctionsCannotBeDisabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rstest::rstest]
+    #[case::linear("{ scale = 0.5, offset = -40 }", 650.0, Some(285.0))]
+    #[case::only_scale("{ scale = 0.1 }", 215.0, Some(21.5))]
+    #[case::arithmetic(r#""value / 10 + 1""#, 215.0, Some(22.5))]
+    #[case::round(r#""round(value)""#, 21.5, Some(22.0))]
+    #[case::min_max(r#""max(min(value, 100), 0)""#, 120.0, Some(100.0))]
+    #[case::clamp(r#""clamp(value, 0, 100)""#, -5.0, Some(0.0))]
+    #[case::abs(r#""abs(value)""#, -5.0, Some(5.0))]
+    #[case::conditional(r#""if(value > 1000, value / 1000, value)""#, 1500.0, Some(1.5))]
+    #[case::comparison(r#""value > 10""#, 15.0, Some(1.0))]
+    #[case::not_finite(r#""value / 0""#, 1.0, None)]
+    #[case::string_result(r#""\"whatever\"""#, 1.0, None)]
+    #[case::unknown_variable(r#""other * 2""#, 1.0, None)]
+    fn apply_works(#[case] transform: &str, #[case] value: f64, #[case] expected: Option<f64>) {
+        let transforms = toml::from_str::<Transforms>(&format!("key = {transform}")).unwrap();
+        assert_eq!(transforms.apply("key", value), expected);
+    }
+
+    #[test]
+    fn apply_other_key_is_unchanged() {
+        let transforms = toml::from_str::<Transforms>(r#""*.raw" = { scale = 2 }"#).unwrap();
+        assert_eq!(transforms.apply("sensor.raw", 2.0), Some(4.0));
+        assert_eq!(transforms.apply("sensor.temperature", 2.0), Some(2.0));
+    }
+
+    #[test]
+    fn apply_first_match_wins() {
+        let transforms = toml::from_str::<Transforms>(
+            r#"
+            "room.temperature" = { scale = 0.1 }
+            "*" = { scale = 100 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(transforms.apply("room.temperature", 200.0), Some(20.0));
+        assert_eq!(transforms.apply("room.humidity", 0.5), Some(50.0));
+    }
+
+    #[test]
+    fn invalid_expression_fails() {
+        let err = toml::from_str::<Transforms>(r#"key = "value * (2""#).unwrap_err();
+        assert!(err.to_string().contains("invalid expression"), "{err}");
+    }
+}
@@ -0,0 +1,277 @@
+//! Values with units like `1.2 kW` or `21.5 °C` and their conversion into a canonical unit
+
+use serde::Deserialize;
+
+use crate::floatify::{Numbers, parse_number};
+use crate::rules::Patterns;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Base {
+    Watt,
+    WattHour,
+    Celsius,
+    Fahrenheit,
+    Percent,
+    Volt,
+    Ampere,
+    Pascal,
+}
+
+impl Base {
+    /// Longer symbols first so `Wh` is not taken for `W` with a leftover
+    const ALL: [(&'static str, Self); 8] = [
+        ("Wh", Self::WattHour),
+        ("W", Self::Watt),
+        ("°C", Self::Celsius),
+        ("°F", Self::Fahrenheit),
+        ("%", Self::Percent),
+        ("V", Self::Volt),
+        ("A", Self::Ampere),
+        ("Pa", Self::Pascal),
+    ];
+
+    const fn symbol(self) -> &'static str {
+        match self {
+            Self::Watt => "W",
+            Self::WattHour => "Wh",
+            Self::Celsius => "°C",
+            Self::Fahrenheit => "°F",
+            Self::Percent => "%",
+            Self::Volt => "V",
+            Self::Ampere => "A",
+            Self::Pascal => "Pa",
+        }
+    }
+
+    /// Units of the same quantity can be converted into each other
+    const fn quantity(self) -> Self {
+        match self {
+            Self::Fahrenheit => Self::Celsius,
+            other => other,
+        }
+    }
+
+    const fn supports_prefix(self) -> bool {
+        !matches!(self, Self::Celsius | Self::Fahrenheit | Self::Percent)
+    }
+}
+
+const PREFIXES: [(char, f64); 5] = [('m', 1e-3), ('h', 1e2), ('k', 1e3), ('M', 1e6), ('G', 1e9)];
+
+/// Unit with an optional SI prefix like `kWh`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    prefix: Option<(char, f64)>,
+    base: Base,
+}
+
+impl std::str::FromStr for Unit {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let base = |symbol: &str| {
+            Base::ALL
+                .iter()
+                .find(|(candidate, _)| *candidate == symbol)
+                .map(|(_, base)| *base)
+        };
+        if let Some(base) = base(input) {
+            return Ok(Self { prefix: None, base });
+        }
+        let mut chars = input.chars();
+        chars
+            .next()
+            .and_then(|first| PREFIXES.iter().find(|(prefix, _)| *prefix == first))
+            .and_then(|prefix| {
+                let base = base(chars.as_str()).filter(|base| base.supports_prefix())?;
+                Some(Self {
+                    prefix: Some(*prefix),
+                    base,
+                })
+            })
+            .ok_or_else(|| format!("unknown unit {input:?}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Unit {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some((prefix, _)) = self.prefix {
+            write!(fmt, "{prefix}")?;
+        }
+        fmt.write_str(self.base.symbol())
+    }
+}
+
+impl Unit {
+    /// Value in the unit without prefix and in °C for temperatures
+    fn to_base(self, value: f64) -> f64 {
+        let value = value * self.prefix.map_or(1.0, |(_, factor)| factor);
+        match self.base {
+            Base::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            _ => value,
+        }
+    }
+
+    /// Inverse of [`Self::to_base`]
+    fn in_unit(self, value: f64) -> f64 {
+        let value = match self.base {
+            Base::Fahrenheit => value.mul_add(9.0 / 5.0, 32.0),
+            _ => value,
+        };
+        value / self.prefix.map_or(1.0, |(_, factor)| factor)
+    }
+
+    /// Convert the value into the other unit when both are of the same quantity
+    pub fn convert(self, value: f64, into: Self) -> Option<f64> {
+        (self.base.quantity() == into.base.quantity()).then(|| into.in_unit(self.to_base(value)))
+    }
+}
+
+/// Split a value like `1.2 kW` or `21.5°C` into the number and its unit
+pub fn parse(input: &str, numbers: Numbers) -> Option<(f64, Unit)> {
+    let input = input.trim();
+    let index = input.find(|char: char| char.is_alphabetic() || char == '°' || char == '%')?;
+    let value = parse_number(input[..index].trim(), numbers)?;
+    let unit = input[index..].trim().parse().ok()?;
+    Some((value, unit))
+}
+
+/// Per rule handling of values with units
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Units {
+    /// Convert the values of matching keys into this unit like `{ power = "W", "*.temperature" = "°C" }`.
+    ///
+    /// Values with a unit of another quantity or an unknown unit are dropped. Values without a unit are assumed to already be in this unit.
+    #[serde(default)]
+    pub canonical: Patterns<Unit>,
+
+    /// Add the unit as `unit` tag
+    #[serde(default)]
+    pub tag: bool,
+}
+
+impl Units {
+    /// Convert the value of the key with its original string representation (if it was a string).
+    ///
+    /// Returns the value and the unit tag or `None` when it does not match the canonical unit.
+    pub fn apply(
+        &self,
+        path: &str,
+        raw: Option<&str>,
+        value: f64,
+        numbers: Numbers,
+    ) -> Option<(f64, Option<Unit>)> {
+        let canonical = self.canonical.find(path).copied();
+        let parsed = raw.and_then(|raw| parse(raw, numbers));
+        let (value, unit) = match (parsed, canonical) {
+            (Some((value, unit)), Some(canonical)) => {
+                (unit.convert(value, canonical)?, Some(canonical))
+            }
+            (Some((value, unit)), None) => (value, Some(unit)),
+            // Strings with unknown units are not trusted to be in the canonical unit
+            (None, Some(_))
+                if raw.is_some_and(|raw| parse_number(raw.trim(), numbers).is_none()) =>
+            {
+                return None;
+            }
+            (None, canonical) => (value, canonical),
+        };
+        Some((value, unit.filter(|_| self.tag)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+
+    #[rstest::rstest]
+    #[case("1.2 kW", 1.2, "kW")]
+    #[case("1200W", 1200.0, "W")]
+    #[case(" 21.5 °C ", 21.5, "°C")]
+    #[case("70°F", 70.0, "°F")]
+    #[case("42 %", 42.0, "%")]
+    #[case("230.1 V", 230.1, "V")]
+    #[case("500 mA", 500.0, "mA")]
+    #[case("1013 hPa", 1013.0, "hPa")]
+    #[case("13.37 kWh", 13.37, "kWh")]
+    fn parse_works(#[case] input: &str, #[case] value: f64, #[case] unit: &str) {
+        let (parsed, parsed_unit) = parse(input, Numbers::default()).unwrap();
+        assert_float_eq!(parsed, value, abs <= 0.001);
+        assert_eq!(parsed_unit.to_string(), unit);
+    }
+
+    #[rstest::rstest]
+    #[case::no_unit("42")]
+    #[case::unknown_unit("42 dB")]
+    #[case::prefix_on_celsius("42 k°C")]
+    #[case::no_number("kW")]
+    fn parse_fails(#[case] input: &str) {
+        assert_eq!(parse(input, Numbers::default()), None);
+    }
+
+    #[rstest::rstest]
+    #[case(1.2, "kW", "W", 1200.0)]
+    #[case(1200.0, "Wh", "kWh", 1.2)]
+    #[case(100.0, "°C", "°F", 212.0)]
+    #[case(32.0, "°F", "°C", 0.0)]
+    #[case(1013.0, "hPa", "Pa", 101_300.0)]
+    fn convert_works(
+        #[case] value: f64,
+        #[case] from: &str,
+        #[case] into: &str,
+        #[case] expected: f64,
+    ) {
+        let from = from.parse::<Unit>().unwrap();
+        let into = into.parse::<Unit>().unwrap();
+        assert_float_eq!(from.convert(value, into).unwrap(), expected, abs <= 0.001);
+    }
+
+    #[test]
+    fn convert_other_quantity_fails() {
+        let from = "kW".parse::<Unit>().unwrap();
+        let into = "kWh".parse::<Unit>().unwrap();
+        assert_eq!(from.convert(1.0, into), None);
+    }
+
+    #[test]
+    fn apply_works() {
+        let units = toml::from_str::<Units>(
+            r#"
+            canonical = { power = "W" }
+            tag = true
+            "#,
+        )
+        .unwrap();
+        let apply = |path, raw, value| {
+            units
+                .apply(path, raw, value, Numbers::default())
+                .map(|(value, unit)| (value, unit.map(|unit| unit.to_string())))
+        };
+        assert_eq!(
+            apply("power", Some("1.2 kW"), 1.2),
+            Some((1200.0, Some("W".into())))
+        );
+        assert_eq!(
+            apply("power", None, 1200.0),
+            Some((1200.0, Some("W".into())))
+        );
+        assert_eq!(apply("power", Some("5 V"), 5.0), None);
+        assert_eq!(apply("power", Some("5 dB"), 5.0), None);
+        assert_eq!(
+            apply("voltage", Some("230 V"), 230.0),
+            Some((230.0, Some("V".into())))
+        );
+        assert_eq!(apply("other", Some("42"), 42.0), Some((42.0, None)));
+    }
+}